@@ -1,7 +1,7 @@
 /// Language adapter trait for translating DSL queries into Tree-sitter queries.
 use crate::dsl::Expr;
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Trait for language-specific adapters.
 pub trait LangAdapter: Send + Sync {
@@ -19,18 +19,39 @@ pub trait LangAdapter: Send + Sync {
     fn post_capture_filter(&self, _caps: &CaptureBundle) -> bool {
         true
     }
+
+    /// Optional hook to add captures that a flat Tree-sitter query cannot
+    /// express (e.g. every method of an interface). `node` is the matched node.
+    fn enrich_captures(&self, _node: tree_sitter::Node, _src: &[u8], _caps: &mut CaptureBundle) {}
+
+    /// Node kinds (functions, methods, classes) whose full range becomes the
+    /// excerpt under `--context-mode block`.
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 /// Bundle of capture texts from a Tree-sitter match.
 #[derive(Debug)]
 pub struct CaptureBundle {
     pub texts: HashMap<String, String>,
+    pub lists: HashMap<String, Vec<String>>,
+    /// Named regex groups captured by the predicates that matched.
+    pub groups: BTreeMap<String, String>,
+    /// Tree-sitter node kinds of a call's arguments, in order.
+    pub arg_kinds: Vec<String>,
+    /// Names called along the fluent chain a call is part of, in source order.
+    pub chain: Vec<String>,
 }
 
 impl CaptureBundle {
     pub fn new() -> Self {
         Self {
             texts: HashMap::new(),
+            lists: HashMap::new(),
+            groups: BTreeMap::new(),
+            arg_kinds: Vec::new(),
+            chain: Vec::new(),
         }
     }
 
@@ -41,6 +62,16 @@ impl CaptureBundle {
     pub fn get(&self, key: &str) -> Option<&str> {
         self.texts.get(key).map(|s| s.as_str())
     }
+
+    /// Append a value to a multi-valued capture.
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.lists.entry(key.into()).or_default().push(value.into());
+    }
+
+    /// All values of a multi-valued capture (empty when absent).
+    pub fn get_list(&self, key: &str) -> &[String] {
+        self.lists.get(key).map(|v| v.as_slice()).unwrap_or(&[])
+    }
 }
 
 impl Default for CaptureBundle {
@@ -3,15 +3,32 @@
 /// Examples:
 /// - call(callee=/^axios\.(get|post)$/)
 /// - import(module=/^requests$/)
+/// - import(alias=/^np$/)
+/// - import(member=/^Session$/)
+/// - call(chain=/^raw$/) (any link of a fluent chain)
 /// - def(name=/^verifyJwt$/)
+/// - impl(trait=/^Display$/, type=/^Foo$/)
+/// - decorator(name=/^Test$/) (alias: annotation)
+/// - literal(value=/^https?:\/\//)
+/// - assign(name=/password/) (alias: assignment)
+/// - call(callee="fetch") (exact string, no regex escaping)
+/// - call(callee=glob:axios.*) (`*` and `?` wildcards, anchored)
+/// - def(!name=/test/) (`!` negates a single predicate)
+/// - def|call(text=/init/) (several kinds sharing one predicate list)
 use regex::{Regex, RegexBuilder};
 use std::fmt;
 
+/// Node kinds addressable by the DSL. Adapters compile kinds that have no
+/// analogue in their language to an empty query list.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Kind {
     Call,
     Import,
     Def,
+    Impl,
+    Decorator,
+    Literal,
+    Assignment,
 }
 
 impl fmt::Display for Kind {
@@ -20,40 +37,91 @@ impl fmt::Display for Kind {
             Kind::Call => write!(f, "call"),
             Kind::Import => write!(f, "import"),
             Kind::Def => write!(f, "def"),
+            Kind::Impl => write!(f, "impl"),
+            Kind::Decorator => write!(f, "decorator"),
+            Kind::Literal => write!(f, "literal"),
+            Kind::Assignment => write!(f, "assign"),
         }
     }
 }
 
+/// Predicate value: a `/regex/`, an exact `"literal"`, or a `glob:pattern`.
 #[derive(Debug, Clone)]
-pub enum Pred {
-    Callee(Regex),
-    Name(Regex),
-    Module(Regex),
-    Prop(Regex),
-    Arg(Regex),
-    Text(Regex),
+pub enum Matcher {
+    Regex(Regex),
+    Literal(String),
+    /// A glob compiled to an anchored regex.
+    Glob(Regex),
 }
 
-impl Pred {
-    pub fn matches(&self, field: &str, text: &str) -> bool {
+impl Matcher {
+    pub fn is_match(&self, text: &str) -> bool {
         match self {
-            Pred::Callee(re) if field == "callee" => re.is_match(text),
-            Pred::Name(re) if field == "name" => re.is_match(text),
-            Pred::Module(re) if field == "module" => re.is_match(text),
-            Pred::Prop(re) if field == "prop" => re.is_match(text),
-            Pred::Arg(re) if field == "arg" => re.is_match(text),
-            Pred::Text(re) if field == "text" => re.is_match(text),
-            _ => false,
+            Matcher::Regex(re) | Matcher::Glob(re) => re.is_match(text),
+            Matcher::Literal(lit) => text == lit,
         }
     }
+
+    /// Named groups of a regex that matches `text`, e.g. `(?P<ver>\d+)`.
+    pub fn named_groups(&self, text: &str) -> Vec<(String, String)> {
+        let Matcher::Regex(re) = self else {
+            return Vec::new();
+        };
+        let Some(caps) = re.captures(text) else {
+            return Vec::new();
+        };
+        re.capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pred {
+    Callee(Matcher),
+    Name(Matcher),
+    Module(Matcher),
+    Prop(Matcher),
+    // Parsed but not yet evaluated; see `apply_predicates`.
+    #[allow(dead_code)]
+    Arg(Matcher),
+    Text(Matcher),
+    Type(Matcher),
+    Trait(Matcher),
+    Method(Matcher),
+    Embed(Matcher),
+    /// Type-parameter list (plus any Rust `where` clause) of a generic
+    /// definition.
+    Generics(Matcher),
+    /// `argkind=N:kind`: the Nth argument (1-based) of a call is a node of
+    /// this tree-sitter kind.
+    ArgKind {
+        index: usize,
+        kind: String,
+    },
+    Value(Matcher),
+    Alias(Matcher),
+    /// Any method called in the fluent chain the matched call belongs to.
+    Chain(Matcher),
+    /// A name a `from x import y` / `import { y } from 'x'` statement binds.
+    Member(Matcher),
+    /// `!field=value`: holds when the inner predicate does not.
+    Not(Box<Pred>),
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Node { kind: Kind, preds: Vec<Pred> },
+    Node {
+        kind: Kind,
+        preds: Vec<Pred>,
+    },
     And(Vec<Expr>),
     Or(Vec<Expr>),
     Not(Box<Expr>),
+    /// A tree-sitter query in the searched language's own syntax, bypassing
+    /// the DSL; its `@match` capture is the result node.
+    Raw(String),
 }
 
 /// Simple DSL parser with boolean combinators.
@@ -146,7 +214,39 @@ impl<'a> Parser<'a> {
         Ok(items)
     }
 
+    /// `kind(preds)`, or `kind|kind|…(preds)` for an `Or` of one node per
+    /// kind, each with the same predicates.
     fn parse_node_expr(&mut self) -> anyhow::Result<Expr> {
+        let mut kinds = vec![self.parse_kind()?];
+        loop {
+            self.skip_ws();
+            if self.peek_char() != Some('|') {
+                break;
+            }
+            self.pos += 1;
+            kinds.push(self.parse_kind()?);
+        }
+
+        let predicates_raw = self.read_group_contents()?;
+        let preds = parse_predicates(&predicates_raw)?;
+        if kinds.len() == 1 {
+            return Ok(Expr::Node {
+                kind: kinds.remove(0),
+                preds,
+            });
+        }
+        Ok(Expr::Or(
+            kinds
+                .into_iter()
+                .map(|kind| Expr::Node {
+                    kind,
+                    preds: preds.clone(),
+                })
+                .collect(),
+        ))
+    }
+
+    fn parse_kind(&mut self) -> anyhow::Result<Kind> {
         self.skip_ws();
 
         let start = self.pos;
@@ -159,36 +259,36 @@ impl<'a> Parser<'a> {
         }
 
         let kind_str = &self.src[start..self.pos];
-        let kind = match kind_str {
+        Ok(match kind_str {
             "call" => Kind::Call,
             "import" => Kind::Import,
             "def" => Kind::Def,
+            "impl" => Kind::Impl,
+            "decorator" | "annotation" => Kind::Decorator,
+            "literal" => Kind::Literal,
+            "assign" | "assignment" => Kind::Assignment,
             "" => anyhow::bail!("Expected expression"),
             other => anyhow::bail!("Unknown kind: {}", other),
-        };
-
-        let predicates_raw = self.read_group_contents()?;
-        let preds = parse_predicates(&predicates_raw)?;
-        Ok(Expr::Node { kind, preds })
+        })
     }
 
     fn read_group_contents(&mut self) -> anyhow::Result<String> {
         self.expect('(')?;
         let mut contents = String::new();
         let mut depth = 1;
-        let mut in_regex = false;
+        let mut values = ValueScanner::default();
 
         while let Some(ch) = self.next_char() {
+            if values.feed(ch) {
+                contents.push(ch);
+                continue;
+            }
             match ch {
-                '/' => {
-                    in_regex = !in_regex;
-                    contents.push(ch);
-                }
-                '(' if !in_regex => {
+                '(' => {
                     depth += 1;
                     contents.push(ch);
                 }
-                ')' if !in_regex => {
+                ')' => {
                     depth -= 1;
                     if depth == 0 {
                         break;
@@ -273,27 +373,47 @@ fn parse_predicates(preds_str: &str) -> anyhow::Result<Vec<Pred>> {
         if let Some(eq_idx) = part.find('=') {
             let field = part[..eq_idx].trim();
             let value = part[eq_idx + 1..].trim();
-
-            let pattern = if value.starts_with('/') && value.ends_with('/') {
-                &value[1..value.len() - 1]
-            } else {
-                anyhow::bail!("Expected regex pattern like /.../ for {}", field);
+            let (negated, field) = match field.strip_prefix('!') {
+                Some(field) => (true, field.trim()),
+                None => (false, field),
             };
 
-            let re = build_regex(field, pattern)
-                .map_err(|e| anyhow::anyhow!("Invalid regex for {}: {}", field, e))?;
+            if field == "argkind" {
+                let pred = parse_arg_kind(value)?;
+                preds.push(if negated {
+                    Pred::Not(Box::new(pred))
+                } else {
+                    pred
+                });
+                continue;
+            }
+
+            let m = parse_matcher(field, value)?;
 
             let pred = match field {
-                "callee" => Pred::Callee(re),
-                "name" => Pred::Name(re),
-                "module" => Pred::Module(re),
-                "prop" => Pred::Prop(re),
-                "arg" => Pred::Arg(re),
-                "text" | "code" => Pred::Text(re),
+                "callee" => Pred::Callee(m),
+                "name" => Pred::Name(m),
+                "module" => Pred::Module(m),
+                "prop" => Pred::Prop(m),
+                "arg" => Pred::Arg(m),
+                "text" | "code" => Pred::Text(m),
+                "type" => Pred::Type(m),
+                "trait" => Pred::Trait(m),
+                "method" => Pred::Method(m),
+                "embed" => Pred::Embed(m),
+                "generics" => Pred::Generics(m),
+                "value" => Pred::Value(m),
+                "alias" => Pred::Alias(m),
+                "member" => Pred::Member(m),
+                "chain" => Pred::Chain(m),
                 _ => anyhow::bail!("Unknown predicate field: {}", field),
             };
 
-            preds.push(pred);
+            preds.push(if negated {
+                Pred::Not(Box::new(pred))
+            } else {
+                pred
+            });
         } else {
             anyhow::bail!("Invalid predicate format: {}", part);
         }
@@ -302,6 +422,125 @@ fn parse_predicates(preds_str: &str) -> anyhow::Result<Vec<Pred>> {
     Ok(preds)
 }
 
+/// Parse the `N:kind` value of an `argkind` predicate.
+fn parse_arg_kind(value: &str) -> anyhow::Result<Pred> {
+    let parsed = value.split_once(':').and_then(|(index, kind)| {
+        let index = index.trim().parse::<usize>().ok().filter(|&i| i >= 1)?;
+        let kind = kind.trim();
+        let valid = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        valid.then(|| Pred::ArgKind {
+            index,
+            kind: kind.to_string(),
+        })
+    });
+    parsed.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Expected argkind=N:kind with a 1-based argument position, e.g. argkind=1:string; got {}",
+            value
+        )
+    })
+}
+
+fn parse_matcher(field: &str, value: &str) -> anyhow::Result<Matcher> {
+    let quoted =
+        |delim: char| value.len() >= 2 && value.starts_with(delim) && value.ends_with(delim);
+
+    if quoted('/') {
+        let re = build_regex(field, &value[1..value.len() - 1])
+            .map_err(|e| anyhow::anyhow!("Invalid regex for {}: {}", field, e))?;
+        Ok(Matcher::Regex(re))
+    } else if quoted('"') {
+        Ok(Matcher::Literal(unescape_literal(
+            &value[1..value.len() - 1],
+        )))
+    } else if let Some(glob) = value.strip_prefix("glob:") {
+        let re = build_regex(field, &glob_to_regex(glob))
+            .map_err(|e| anyhow::anyhow!("Invalid glob for {}: {}", field, e))?;
+        Ok(Matcher::Glob(re))
+    } else {
+        anyhow::bail!(
+            "Expected regex pattern like /.../, literal like \"...\", or glob:... for {}",
+            field
+        );
+    }
+}
+
+/// Translate a glob into an anchored regex: `*` matches any run of
+/// characters, `?` any single character, and everything else is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    for ch in glob.chars() {
+        match ch {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push('$');
+    pattern
+}
+
+/// Resolve `\"` and `\\` escapes inside a quoted literal.
+fn unescape_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+                continue;
+            }
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Tracks whether a scanner is inside a `/regex/` or `"literal"` predicate
+/// value, where commas and parentheses are not structural.
+#[derive(Default)]
+struct ValueScanner {
+    in_regex: bool,
+    in_string: bool,
+    escaped: bool,
+    /// The last non-space character was `=`, so a `/` here opens a regex
+    /// rather than being part of e.g. a glob.
+    after_eq: bool,
+}
+
+impl ValueScanner {
+    /// Feed one character; returns true if it belongs to a value (delimiters included).
+    fn feed(&mut self, ch: char) -> bool {
+        let after_eq = self.after_eq;
+        if !ch.is_whitespace() {
+            self.after_eq = ch == '=';
+        }
+
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if ch == '\\' {
+                self.escaped = true;
+            } else if ch == '"' {
+                self.in_string = false;
+            }
+            return true;
+        }
+
+        match ch {
+            '/' if self.in_regex || after_eq => {
+                self.in_regex = !self.in_regex;
+                true
+            }
+            '"' if !self.in_regex => {
+                self.in_string = true;
+                true
+            }
+            _ => self.in_regex,
+        }
+    }
+}
+
 fn build_regex(field: &str, pattern: &str) -> Result<Regex, regex::Error> {
     let mut builder = RegexBuilder::new(pattern);
     if matches!(field, "text" | "code") {
@@ -310,19 +549,19 @@ fn build_regex(field: &str, pattern: &str) -> Result<Regex, regex::Error> {
     builder.build()
 }
 
-/// Split predicates by commas, respecting regex delimiters.
+/// Split predicates by commas, respecting regex and literal delimiters.
 fn split_predicates(s: &str) -> Vec<String> {
     let mut parts = Vec::new();
     let mut current = String::new();
-    let mut in_regex = false;
+    let mut values = ValueScanner::default();
 
     for ch in s.chars() {
+        if values.feed(ch) {
+            current.push(ch);
+            continue;
+        }
         match ch {
-            '/' => {
-                in_regex = !in_regex;
-                current.push(ch);
-            }
-            ',' if !in_regex => {
+            ',' => {
                 if !current.trim().is_empty() {
                     parts.push(current.trim().to_string());
                     current.clear();
@@ -362,6 +601,126 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_impl() {
+        let expr = parse_query("impl(trait=/^Display$/, type=/^Foo$/)").unwrap();
+        if let Expr::Node { kind, preds } = expr {
+            assert_eq!(kind, Kind::Impl);
+            assert!(matches!(preds[0], Pred::Trait(_)));
+            assert!(matches!(preds[1], Pred::Type(_)));
+        } else {
+            panic!("Expected Node");
+        }
+    }
+
+    #[test]
+    fn test_parse_annotation_alias() {
+        for query in ["decorator(name=/^Test$/)", "annotation(name=/^Test$/)"] {
+            match parse_query(query).unwrap() {
+                Expr::Node { kind, .. } => assert_eq!(kind, Kind::Decorator),
+                _ => panic!("Expected Node"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_literal_predicate_is_exact() {
+        let expr = parse_query(r#"call(callee="requests.get")"#).unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        let Pred::Callee(m) = &preds[0] else {
+            panic!("Expected callee predicate");
+        };
+        assert!(matches!(m, Matcher::Literal(_)));
+        assert!(m.is_match("requests.get"));
+        assert!(!m.is_match("requests_get"));
+        assert!(!m.is_match("requests.get_all"));
+    }
+
+    #[test]
+    fn test_literal_may_contain_delimiters() {
+        let expr = parse_query(r#"import(module="./a/b(c), \"d\"", name=/x/)"#).unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        assert_eq!(preds.len(), 2);
+        let Pred::Module(m) = &preds[0] else {
+            panic!("Expected module predicate");
+        };
+        assert!(m.is_match(r#"./a/b(c), "d""#));
+    }
+
+    #[test]
+    fn test_glob_predicate_is_anchored() {
+        let expr = parse_query("call(callee=glob:axios.*, name=/x/)").unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        assert_eq!(preds.len(), 2);
+        let Pred::Callee(m) = &preds[0] else {
+            panic!("Expected callee predicate");
+        };
+        assert!(matches!(m, Matcher::Glob(_)));
+        assert!(m.is_match("axios.get"));
+        assert!(m.is_match("axios.post"));
+        assert!(!m.is_match("myaxios.get"));
+        assert!(!m.is_match("axios_get"));
+    }
+
+    #[test]
+    fn test_glob_may_contain_slashes() {
+        let expr = parse_query("import(module=glob:./api/*, name=/^c?lient$/)").unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        let Pred::Module(m) = &preds[0] else {
+            panic!("Expected module predicate");
+        };
+        assert!(m.is_match("./api/client"));
+        assert!(!m.is_match("./apix/client"));
+        assert!(matches!(preds[1], Pred::Name(Matcher::Regex(_))));
+    }
+
+    #[test]
+    fn test_negated_predicate() {
+        let expr = parse_query("call(callee=/exec/, ! name=/^x$/)").unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        assert!(matches!(preds[0], Pred::Callee(_)));
+        let Pred::Not(inner) = &preds[1] else {
+            panic!("Expected negated predicate");
+        };
+        assert!(matches!(**inner, Pred::Name(_)));
+        assert!(parse_query("def(!bogus=/x/)").is_err());
+    }
+
+    #[test]
+    fn test_parse_arg_kind() {
+        let expr = parse_query("call(callee=/^execute$/, !argkind=1:string)").unwrap();
+        let Expr::Node { preds, .. } = expr else {
+            panic!("Expected Node");
+        };
+        let Pred::Not(inner) = &preds[1] else {
+            panic!("Expected negated predicate");
+        };
+        assert!(matches!(&**inner, Pred::ArgKind { index: 1, kind } if kind == "string"));
+        for bad in [
+            "argkind=0:string",
+            "argkind=string",
+            "argkind=1:",
+            "argkind=x:string",
+        ] {
+            assert!(parse_query(&format!("call({bad})")).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_unquoted_value_rejected() {
+        assert!(parse_query("call(callee=fetch)").is_err());
+    }
+
     #[test]
     fn test_parse_multiple_preds() {
         let expr = parse_query("call(callee=/foo/, arg=/bar/)").unwrap();
@@ -408,4 +767,30 @@ mod tests {
     fn test_not_requires_single_operand() {
         assert!(parse_query("not(call(callee=/foo/), call(callee=/bar/))").is_err());
     }
+
+    #[test]
+    fn test_parse_multi_kind() {
+        let expr = parse_query("def | call(text=/init/)").unwrap();
+        let Expr::Or(nodes) = expr else {
+            panic!("Expected Or expression");
+        };
+        let kinds: Vec<_> = nodes
+            .iter()
+            .map(|node| match node {
+                Expr::Node { kind, preds } => {
+                    assert_eq!(preds.len(), 1);
+                    kind.clone()
+                }
+                _ => panic!("Expected node"),
+            })
+            .collect();
+        assert_eq!(kinds, [Kind::Def, Kind::Call]);
+
+        assert!(matches!(
+            parse_query("call(callee=/a|b/)").unwrap(),
+            Expr::Node { .. }
+        ));
+        assert!(parse_query("def|(name=/x/)").is_err());
+        assert!(parse_query("def|bogus(name=/x/)").is_err());
+    }
 }
@@ -0,0 +1,100 @@
+/// Golden-file tests: every adapter runs a fixed set of queries over its
+/// fixture in `src/test_source/golden/` and the matches are compared with the
+/// checked-in `<fixture>.ndjson`.
+///
+/// After an intended change to an adapter, regenerate the goldens with
+/// `UPDATE_GOLDEN=1 cargo test -p ast-find golden` and review the diff.
+use crate::dsl::parse_query;
+use crate::languages::{get_adapter, lang_for_path};
+use crate::processor::{process_file, ProcessOptions};
+use std::path::{Path, PathBuf};
+
+const QUERIES: &[&str] = &["call()", "import()", "def()"];
+
+const FIXTURES: &[&str] = &[
+    "sample.py",
+    "sample.js",
+    "sample.ts",
+    "sample.rs",
+    "sample.go",
+    "sample.java",
+    "sample.cs",
+    "sample.php",
+];
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/test_source/golden")
+}
+
+/// One NDJSON line per match, ordered by position then query. Only fields
+/// that do not depend on where the fixture lives on disk are kept.
+fn render(fixture: &Path) -> String {
+    let lang = lang_for_path(fixture).expect("fixture language");
+    let adapter = get_adapter(lang);
+
+    let mut lines = Vec::new();
+    for query in QUERIES {
+        let expr = parse_query(query).expect("parse query");
+        let records = process_file(adapter.as_ref(), fixture, &expr, &ProcessOptions::default())
+            .expect("process fixture");
+        for record in records {
+            let line = serde_json::json!({
+                "query": query,
+                "start_line": record.start_line,
+                "end_line": record.end_line,
+                "enclosing": record.enclosing,
+                "capture": record.capture,
+            });
+            lines.push((
+                (record.start_line, record.end_line, *query),
+                line.to_string(),
+            ));
+        }
+    }
+    lines.sort();
+
+    let mut out: String = lines
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    out.push('\n');
+    out
+}
+
+#[test]
+fn adapters_match_golden_output() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatched = Vec::new();
+
+    for name in FIXTURES {
+        let fixture = golden_dir().join(name);
+        let golden = golden_dir().join(format!("{name}.ndjson"));
+        let actual = render(&fixture);
+
+        if update {
+            std::fs::write(&golden, &actual).expect("write golden");
+            continue;
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+        if actual != expected {
+            let first_diff = actual
+                .lines()
+                .zip(expected.lines())
+                .position(|(a, e)| a != e)
+                .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+            mismatched.push(format!(
+                "{name}: first difference at line {}\n  actual:   {}\n  expected: {}",
+                first_diff + 1,
+                actual.lines().nth(first_diff).unwrap_or("<end>"),
+                expected.lines().nth(first_diff).unwrap_or("<end>"),
+            ));
+        }
+    }
+
+    assert!(
+        mismatched.is_empty(),
+        "golden output changed (rerun with UPDATE_GOLDEN=1 to accept):\n{}",
+        mismatched.join("\n")
+    );
+}
@@ -0,0 +1,116 @@
+/// Terminal-friendly rendering of matches, in the style of `rg`/`grep -n`:
+/// a `path:line:` header per match, then its excerpt with line numbers
+/// (`:` after matched lines, `-` after context lines) and `--` between
+/// matches.
+use crate::processor::MatchRecord;
+use std::io::{self, Write};
+
+const PATH: &str = "\x1b[35m";
+const LINE: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Writes matches one after another, remembering whether a separator is due.
+pub struct HumanWriter {
+    color: bool,
+    first: bool,
+}
+
+impl HumanWriter {
+    /// `color` adds ANSI colors to paths and line numbers.
+    pub fn new(color: bool) -> Self {
+        Self { color, first: true }
+    }
+
+    pub fn write_match<W: Write>(&mut self, out: &mut W, record: &MatchRecord) -> io::Result<()> {
+        if !std::mem::take(&mut self.first) {
+            writeln!(out, "--")?;
+        }
+        let (path, line, reset) = self.colors();
+        writeln!(
+            out,
+            "{path}{}{reset}:{line}{}{reset}:",
+            record.path, record.start_line
+        )?;
+
+        let excerpt = record.excerpt.as_deref().unwrap_or_default();
+        for (number, text) in (record.excerpt_line..).zip(excerpt.lines()) {
+            let marker = if (record.start_line..=record.end_line).contains(&number) {
+                ':'
+            } else {
+                '-'
+            };
+            writeln!(out, "{line}{number}{reset}{marker}{text}")?;
+        }
+        Ok(())
+    }
+
+    /// A file that could not be searched, as `path: error: message`.
+    pub fn write_error<W: Write>(
+        &mut self,
+        out: &mut W,
+        path: Option<&str>,
+        message: &str,
+    ) -> io::Result<()> {
+        let (color, _, reset) = self.colors();
+        match path {
+            Some(path) => writeln!(out, "{color}{path}{reset}: error: {message}"),
+            None => writeln!(out, "error: {message}"),
+        }
+    }
+
+    fn colors(&self) -> (&'static str, &'static str, &'static str) {
+        if self.color {
+            (PATH, LINE, RESET)
+        } else {
+            ("", "", "")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(start_line: u32, excerpt: &str, excerpt_line: u32) -> MatchRecord {
+        MatchRecord {
+            lang: Some("python".to_string()),
+            path: "./app.py".to_string(),
+            start_line,
+            end_line: start_line,
+            start_byte: 0,
+            end_byte: 0,
+            chunk_id: String::new(),
+            score: 1.0,
+            excerpt: Some(excerpt.to_string()),
+            excerpt_line,
+            capture: serde_json::Value::Null,
+            enclosing: None,
+        }
+    }
+
+    #[test]
+    fn matches_are_numbered_and_separated() {
+        let mut out = Vec::new();
+        let mut writer = HumanWriter::new(false);
+        writer
+            .write_match(&mut out, &record(9, "a = 1\nfetch()\nb = 2\n", 8))
+            .unwrap();
+        writer
+            .write_match(&mut out, &record(12, "fetch()\n", 12))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "./app.py:9:\n8-a = 1\n9:fetch()\n10-b = 2\n--\n./app.py:12:\n12:fetch()\n"
+        );
+    }
+
+    #[test]
+    fn color_wraps_path_and_line_numbers() {
+        let mut out = Vec::new();
+        HumanWriter::new(true)
+            .write_match(&mut out, &record(3, "fetch()\n", 3))
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\x1b[35m./app.py\x1b[0m:\x1b[32m3\x1b[0m:"));
+    }
+}
@@ -0,0 +1,97 @@
+/// Tree-sitter query text shared between adapters whose grammars overlap.
+use crate::dsl::Kind;
+
+/// Function and class node kinds shared by the JavaScript and TypeScript
+/// grammars, used for `--context-mode block`.
+pub const ECMASCRIPT_BLOCKS: &[&str] = &[
+    "function_declaration",
+    "function_expression",
+    "generator_function_declaration",
+    "arrow_function",
+    "method_definition",
+    "class_declaration",
+];
+
+/// Queries valid in both the JavaScript and TypeScript grammars. Returns
+/// `None` for kinds with no ECMAScript analogue.
+pub fn ecmascript(kind: &Kind) -> Option<&'static str> {
+    let query = match kind {
+        Kind::Call => {
+            // Match both identifier calls (foo(...)) and member calls (obj.prop(...))
+            r#"
+            (call_expression
+              function: (identifier) @callee_id
+            ) @call
+
+            (call_expression
+              function: (member_expression
+                object: (_) @obj
+                property: (property_identifier) @prop
+              )
+            ) @call
+            "#
+        }
+        Kind::Import => {
+            r#"
+            (import_statement
+              source: (string) @module
+            ) @import
+            "#
+        }
+        Kind::Def => {
+            r#"
+            (function_declaration
+              name: (identifier) @name
+            ) @def
+
+            (lexical_declaration
+              (variable_declarator
+                name: (identifier) @name
+                value: [(arrow_function) (function_expression)]
+              )
+            ) @def
+            "#
+        }
+        Kind::Impl => return None,
+        Kind::Decorator => {
+            r#"
+            (decorator
+              [(identifier) (member_expression)] @name
+            ) @decorator
+
+            (decorator
+              (call_expression
+                function: (_) @name
+                arguments: (arguments) @args
+              )
+            ) @decorator
+            "#
+        }
+        Kind::Literal => {
+            r#"
+            [(string) (template_string) (number)] @literal
+            "#
+        }
+        Kind::Assignment => {
+            r#"
+            (variable_declarator
+              name: (identifier) @name
+              value: (_) @value
+            ) @assign
+
+            (assignment_expression
+              left: (identifier) @name
+              right: (_) @value
+            ) @assign
+
+            (assignment_expression
+              left: (member_expression
+                property: (property_identifier) @name
+              )
+              right: (_) @value
+            ) @assign
+            "#
+        }
+    };
+    Some(query)
+}
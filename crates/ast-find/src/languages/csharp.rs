@@ -14,6 +14,18 @@ impl LangAdapter for CSharpAdapter {
         tree_sitter_c_sharp::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &[
+            "method_declaration",
+            "constructor_declaration",
+            "local_function_statement",
+            "class_declaration",
+            "struct_declaration",
+            "interface_declaration",
+            "record_declaration",
+        ]
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
@@ -64,11 +76,7 @@ impl LangAdapter for CSharpAdapter {
                     Kind::Import => {
                         r#"
                         (using_directive
-                          (_name) @module
-                        ) @import
-
-                        (using_directive
-                          (type) @module
+                          [(identifier) (qualified_name)] @module
                         ) @import
                         "#
                     }
@@ -107,6 +115,19 @@ impl LangAdapter for CSharpAdapter {
                         ) @def
                         "#
                     }
+                    Kind::Impl | Kind::Decorator | Kind::Assignment => return Ok(Vec::new()),
+                    Kind::Literal => {
+                        r#"
+                        [
+                          (string_literal)
+                          (verbatim_string_literal)
+                          (raw_string_literal)
+                          (interpolated_string_expression)
+                          (integer_literal)
+                          (real_literal)
+                        ] @literal
+                        "#
+                    }
                 };
 
                 let lang = self.language();
@@ -1,6 +1,7 @@
 /// Go language adapter.
-use crate::adapter::LangAdapter;
+use crate::adapter::{CaptureBundle, LangAdapter};
 use crate::dsl::{Expr, Kind};
+use crate::processor::node_text;
 use anyhow::Result;
 
 pub struct GoAdapter;
@@ -14,6 +15,15 @@ impl LangAdapter for GoAdapter {
         tree_sitter_go::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &[
+            "function_declaration",
+            "method_declaration",
+            "func_literal",
+            "type_declaration",
+        ]
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
@@ -66,6 +76,17 @@ impl LangAdapter for GoAdapter {
                         ) @def
                         "#
                     }
+                    Kind::Impl | Kind::Decorator | Kind::Assignment => return Ok(Vec::new()),
+                    Kind::Literal => {
+                        r#"
+                        [
+                          (interpreted_string_literal)
+                          (raw_string_literal)
+                          (int_literal)
+                          (float_literal)
+                        ] @literal
+                        "#
+                    }
                 };
 
                 let lang = self.language();
@@ -75,4 +96,139 @@ impl LangAdapter for GoAdapter {
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    /// Expose interface method sets and embedded types of `type_spec` defs as
+    /// the multi-valued `method` and `embed` captures, and the `[T any]`
+    /// list of generic functions and types as `generics`.
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        if let Some(params) = node.child_by_field_name("type_parameters") {
+            caps.insert("generics", node_text(src, &params));
+        }
+        if node.kind() != "type_spec" {
+            return;
+        }
+        let Some(ty) = node.child_by_field_name("type") else {
+            return;
+        };
+
+        let mut cursor = ty.walk();
+        match ty.kind() {
+            "interface_type" => {
+                for child in ty.named_children(&mut cursor) {
+                    match child.kind() {
+                        "method_elem" => {
+                            if let Some(name) = child.child_by_field_name("name") {
+                                caps.push("method", node_text(src, &name));
+                            }
+                        }
+                        "type_elem" => caps.push("embed", node_text(src, &child)),
+                        _ => {}
+                    }
+                }
+            }
+            "struct_type" => {
+                let Some(fields) = ty.named_child(0) else {
+                    return;
+                };
+                let mut cursor = fields.walk();
+                for field in fields.named_children(&mut cursor) {
+                    // Embedded fields are declarations without a name.
+                    if field.kind() == "field_declaration"
+                        && field.child_by_field_name("name").is_none()
+                    {
+                        if let Some(embedded) = field.child_by_field_name("type") {
+                            caps.push("embed", node_text(src, &embedded));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::test_util::run_query;
+
+    const SOURCE: &str = r#"package io
+
+type Reader interface {
+	Read(p []byte) (n int, err error)
+}
+
+type Closer interface {
+	Close() error
+}
+
+type ReadCloser interface {
+	Reader
+	Closer
+}
+
+type bufferedReader struct {
+	*bufio.Reader
+	io.Closer
+	size int
+}
+
+type Stack[T comparable] struct {
+	items []T
+}
+
+func Map[T any, U fmt.Stringer](xs []T, f func(T) U) []U {
+	return nil
+}
+"#;
+
+    fn run(query: &str) -> Vec<serde_json::Value> {
+        run_query(&GoAdapter, SOURCE, query)
+    }
+
+    #[test]
+    fn interface_method_set_is_captured() {
+        let captures = run("def(name=/^Reader$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["methods"], serde_json::json!(["Read"]));
+    }
+
+    #[test]
+    fn method_predicate_matches_declaring_interface() {
+        let captures = run("def(method=/^Read$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "Reader");
+    }
+
+    #[test]
+    fn embedded_types_are_captured() {
+        let captures = run("def(name=/^ReadCloser$/)");
+        assert_eq!(
+            captures[0]["embeds"],
+            serde_json::json!(["Reader", "Closer"])
+        );
+
+        let captures = run("def(embed=/bufio\\.Reader/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "bufferedReader");
+        assert_eq!(
+            captures[0]["embeds"],
+            serde_json::json!(["bufio.Reader", "io.Closer"])
+        );
+    }
+
+    #[test]
+    fn type_parameter_lists_are_matched() {
+        let captures = run("def(generics=/Stringer/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "Map");
+        assert_eq!(captures[0]["generics"], "[T any, U fmt.Stringer]");
+
+        let captures = run("def(generics=/comparable/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "Stack");
+
+        let captures = run("def(name=/^Reader$/)");
+        assert!(captures[0]["generics"].is_null());
+    }
 }
@@ -1,6 +1,7 @@
 /// Java language adapter.
-use crate::adapter::LangAdapter;
+use crate::adapter::{CaptureBundle, LangAdapter};
 use crate::dsl::{Expr, Kind};
+use crate::processor::node_text;
 use anyhow::Result;
 
 pub struct JavaAdapter;
@@ -14,6 +15,17 @@ impl LangAdapter for JavaAdapter {
         tree_sitter_java::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &[
+            "method_declaration",
+            "constructor_declaration",
+            "class_declaration",
+            "interface_declaration",
+            "enum_declaration",
+            "record_declaration",
+        ]
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
@@ -26,9 +38,9 @@ impl LangAdapter for JavaAdapter {
                         ) @call
 
                         (method_invocation
+                          !object
                           name: (identifier) @callee_id
                         ) @call
-                        (#not-match? @call "\.")
                         "#
                     }
                     Kind::Import => {
@@ -74,6 +86,32 @@ impl LangAdapter for JavaAdapter {
                         ) @def
                         "#
                     }
+                    Kind::Impl | Kind::Assignment => return Ok(Vec::new()),
+                    Kind::Decorator => {
+                        r#"
+                        (annotation
+                          name: (_) @name
+                          arguments: (annotation_argument_list) @args
+                        ) @annotation
+
+                        (marker_annotation
+                          name: (_) @name
+                        ) @annotation
+                        "#
+                    }
+                    Kind::Literal => {
+                        r#"
+                        [
+                          (string_literal)
+                          (decimal_integer_literal)
+                          (hex_integer_literal)
+                          (octal_integer_literal)
+                          (binary_integer_literal)
+                          (decimal_floating_point_literal)
+                          (hex_floating_point_literal)
+                        ] @literal
+                        "#
+                    }
                 };
 
                 let lang = self.language();
@@ -83,4 +121,79 @@ impl LangAdapter for JavaAdapter {
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    /// Capture the name of the element an annotation is attached to as `target`.
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        if !matches!(node.kind(), "annotation" | "marker_annotation") {
+            return;
+        }
+        let Some(decl) = node
+            .parent()
+            .filter(|p| p.kind() == "modifiers")
+            .and_then(|m| m.parent())
+        else {
+            return;
+        };
+
+        // Fields and locals name their variables through a declarator.
+        let name = decl.child_by_field_name("name").or_else(|| {
+            decl.child_by_field_name("declarator")
+                .and_then(|d| d.child_by_field_name("name"))
+        });
+        if let Some(name) = name {
+            caps.insert("target", node_text(src, &name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::test_util::run_query;
+
+    const SOURCE: &str = r#"package com.example;
+
+@RestController
+public class CalculatorTest {
+    @Autowired
+    private Calculator calculator;
+
+    @Test
+    public void testAdd() {
+        assertEquals(4, calculator.add(2, 2));
+    }
+
+    @Transactional(readOnly = true)
+    public int total() {
+        return 0;
+    }
+}
+"#;
+
+    fn run(query: &str) -> Vec<serde_json::Value> {
+        run_query(&JavaAdapter, SOURCE, query)
+    }
+
+    #[test]
+    fn test_annotation_on_method() {
+        let captures = run("annotation(name=/^Test$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "Test");
+        assert_eq!(captures[0]["target"], "testAdd");
+    }
+
+    #[test]
+    fn annotation_arguments_and_targets() {
+        let captures = run("annotation(name=/^Transactional$/)");
+        assert_eq!(captures[0]["args"], "(readOnly = true)");
+        assert_eq!(captures[0]["target"], "total");
+
+        let captures = run("annotation(name=/^(RestController|Autowired)$/)");
+        let mut targets: Vec<_> = captures
+            .iter()
+            .filter_map(|c| c["target"].as_str())
+            .collect();
+        targets.sort();
+        assert_eq!(targets, vec!["CalculatorTest", "calculator"]);
+    }
 }
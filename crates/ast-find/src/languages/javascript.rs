@@ -1,6 +1,8 @@
+use super::common_queries;
 /// JavaScript/TypeScript language adapter.
-use crate::adapter::LangAdapter;
+use crate::adapter::{CaptureBundle, LangAdapter};
 use crate::dsl::{Expr, Kind};
+use crate::processor::node_text;
 use anyhow::Result;
 
 pub struct JavaScriptAdapter;
@@ -14,46 +16,15 @@ impl LangAdapter for JavaScriptAdapter {
         tree_sitter_javascript::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        common_queries::ECMASCRIPT_BLOCKS
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
-                let query_str = match kind {
-                    Kind::Call => {
-                        // Match both identifier calls (foo(...)) and member calls (obj.prop(...))
-                        r#"
-                        (call_expression
-                          function: (identifier) @callee_id
-                        ) @call
-
-                        (call_expression
-                          function: (member_expression
-                            object: (_) @obj
-                            property: (property_identifier) @prop
-                          )
-                        ) @call
-                        "#
-                    }
-                    Kind::Import => {
-                        r#"
-                        (import_statement
-                          source: (string) @module
-                        ) @import
-                        "#
-                    }
-                    Kind::Def => {
-                        r#"
-                        (function_declaration
-                          name: (identifier) @name
-                        ) @def
-
-                        (lexical_declaration
-                          (variable_declarator
-                            name: (identifier) @name
-                            value: [(arrow_function) (function_expression)]
-                          )
-                        ) @def
-                        "#
-                    }
+                let Some(query_str) = common_queries::ecmascript(kind) else {
+                    return Ok(Vec::new());
                 };
 
                 let lang = self.language();
@@ -63,8 +34,40 @@ impl LangAdapter for JavaScriptAdapter {
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        capture_decorator_target(node, src, caps);
+        capture_import_aliases(node, src, caps);
+    }
 }
 
+/// Declarations that only exist in TypeScript, appended to the shared `def` query.
+const TS_DEFS: &str = r#"
+(function_signature
+  name: (identifier) @name
+) @def
+
+(class_declaration
+  name: (type_identifier) @name
+) @def
+
+(abstract_class_declaration
+  name: (type_identifier) @name
+) @def
+
+(interface_declaration
+  name: (type_identifier) @name
+) @def
+
+(type_alias_declaration
+  name: (type_identifier) @name
+) @def
+
+(enum_declaration
+  name: (identifier) @name
+) @def
+"#;
+
 pub struct TypeScriptAdapter;
 
 impl LangAdapter for TypeScriptAdapter {
@@ -76,53 +79,274 @@ impl LangAdapter for TypeScriptAdapter {
         tree_sitter_typescript::language_typescript()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        common_queries::ECMASCRIPT_BLOCKS
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
-        // For now, use same queries as JavaScript
+        // JavaScript queries plus TypeScript-only declarations under `def`.
         match expr {
             Expr::Node { kind, .. } => {
+                let Some(shared) = common_queries::ecmascript(kind) else {
+                    return Ok(Vec::new());
+                };
                 let query_str = match kind {
-                    Kind::Call => {
-                        r#"
-                        (call_expression
-                          function: (identifier) @callee_id
-                        ) @call
-
-                        (call_expression
-                          function: (member_expression
-                            object: (_) @obj
-                            property: (property_identifier) @prop
-                          )
-                        ) @call
-                        "#
-                    }
-                    Kind::Import => {
-                        r#"
-                        (import_statement
-                          source: (string) @module
-                        ) @import
-                        "#
-                    }
-                    Kind::Def => {
-                        r#"
-                        (function_declaration
-                          name: (identifier) @name
-                        ) @def
-
-                        (lexical_declaration
-                          (variable_declarator
-                            name: (identifier) @name
-                            value: [(arrow_function) (function_expression)]
-                          )
-                        ) @def
-                        "#
-                    }
+                    Kind::Def => format!("{shared}{TS_DEFS}"),
+                    _ => shared.to_string(),
                 };
 
                 let lang = self.language();
-                let query = tree_sitter::Query::new(&lang, query_str)?;
+                let query = tree_sitter::Query::new(&lang, &query_str)?;
                 Ok(vec![query])
             }
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        capture_decorator_target(node, src, caps);
+        capture_import_aliases(node, src, caps);
+    }
+}
+
+/// Capture the local names of `{ x as y }` and `* as ns` imports as `alias`,
+/// and the exported names `{ x, y as z }` import as `member`.
+fn capture_import_aliases(node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+    if node.kind() != "import_statement" {
+        return;
+    }
+    let mut cursor = node.walk();
+    let Some(clause) = node
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "import_clause")
+    else {
+        return;
+    };
+
+    let mut cursor = clause.walk();
+    for child in clause.named_children(&mut cursor) {
+        match child.kind() {
+            "namespace_import" => {
+                if let Some(name) = child.named_child(0) {
+                    caps.push("alias", node_text(src, &name));
+                }
+            }
+            "named_imports" => {
+                let mut specifiers = child.walk();
+                for specifier in child.named_children(&mut specifiers) {
+                    if let Some(name) = specifier.child_by_field_name("name") {
+                        caps.push("member", node_text(src, &name));
+                    }
+                    if let Some(alias) = specifier.child_by_field_name("alias") {
+                        caps.push("alias", node_text(src, &alias));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Capture the decorated class, method, or field name as `target`.
+///
+/// The TypeScript grammar places member decorators in the class body ahead of
+/// the member, while JavaScript nests them inside it.
+fn capture_decorator_target(node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+    if node.kind() != "decorator" {
+        return;
+    }
+    let Some(parent) = node.parent() else {
+        return;
+    };
+
+    let owner = if parent.kind() == "class_body" {
+        let mut next = node.next_named_sibling();
+        while let Some(sibling) = next.filter(|n| n.kind() == "decorator") {
+            next = sibling.next_named_sibling();
+        }
+        next
+    } else {
+        Some(parent)
+    };
+
+    if let Some(name) = owner.and_then(|o| o.child_by_field_name("name")) {
+        caps.insert("target", node_text(src, &name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::parse_query;
+    use crate::languages::test_util::run_query;
+    use crate::processor::{process_file, ProcessOptions};
+
+    fn fixture_lines(query: &str) -> Vec<(u32, String)> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/test_source/test.js");
+        let expr = parse_query(query).expect("parse query");
+        let mut found: Vec<_> =
+            process_file(&JavaScriptAdapter, &path, &expr, &ProcessOptions::default())
+                .expect("process fixture")
+                .into_iter()
+                .map(|r| {
+                    let label = ["callee", "module", "name"]
+                        .iter()
+                        .find_map(|k| r.capture[*k].as_str())
+                        .unwrap_or_default()
+                        .to_string();
+                    (r.start_line, label)
+                })
+                .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn javascript_fixture_matches_are_stable() {
+        let owned = |items: &[(u32, &str)]| -> Vec<(u32, String)> {
+            items.iter().map(|(l, s)| (*l, s.to_string())).collect()
+        };
+        assert_eq!(
+            fixture_lines("call(callee=/./)"),
+            owned(&[(7, "get"), (12, "post"), (17, "log")])
+        );
+        assert_eq!(
+            fixture_lines("import(module=/./)"),
+            owned(&[(3, "'axios'"), (4, "'requests'")])
+        );
+        assert_eq!(
+            fixture_lines("def(name=/./)"),
+            owned(&[(6, "fetchData"), (11, "postData"), (16, "regularFunction")])
+        );
+    }
+
+    #[test]
+    fn typescript_type_declarations_are_defs() {
+        let source = r#"
+import type { ReactNode } from 'react';
+
+export interface ButtonProps {
+  label: string;
+}
+
+type LinkProps = ButtonProps & { href: string };
+
+enum Color {
+  Red,
+  Green,
+}
+
+abstract class Shape {}
+class Circle extends Shape {}
+"#;
+        let captures = run_query(
+            &TypeScriptAdapter,
+            source,
+            "def(name=/^(ButtonProps|LinkProps|Color|Shape|Circle)$/)",
+        );
+        let mut names: Vec<_> = captures.iter().filter_map(|c| c["name"].as_str()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["ButtonProps", "Circle", "Color", "LinkProps", "Shape"]
+        );
+
+        let imports = run_query(&TypeScriptAdapter, source, "import(module=/react/)");
+        assert_eq!(imports.len(), 1);
+    }
+
+    #[test]
+    fn typescript_decorators_capture_target() {
+        let source = r#"
+@Component({ selector: 'app' })
+class AppComponent {
+  @Input() title: string;
+
+  @HostListener('click')
+  onClick() {}
+}
+"#;
+        let captures = run_query(&TypeScriptAdapter, source, "decorator(name=/./)");
+        let mut targets: Vec<_> = captures
+            .iter()
+            .filter_map(|c| c["target"].as_str())
+            .collect();
+        targets.sort();
+        assert_eq!(targets, vec!["AppComponent", "onClick", "title"]);
+    }
+
+    #[test]
+    fn named_and_namespace_imports_capture_aliases() {
+        let source = r#"
+import { foo as bar, baz } from './util';
+import * as path from 'path';
+import React from 'react';
+"#;
+        for adapter in [&JavaScriptAdapter as &dyn LangAdapter, &TypeScriptAdapter] {
+            let captures = run_query(adapter, source, "import(alias=/^bar$/)");
+            assert_eq!(captures.len(), 1);
+            assert_eq!(captures[0]["module"], "'./util'");
+            assert_eq!(captures[0]["aliases"], serde_json::json!(["bar"]));
+
+            let captures = run_query(adapter, source, "import(alias=/^path$/)");
+            assert_eq!(captures[0]["module"], "'path'");
+
+            assert!(run_query(adapter, source, "import(alias=/^(baz|React)$/)").is_empty());
+        }
+    }
+
+    #[test]
+    fn named_imports_capture_members() {
+        let source = r#"
+import { Session } from './db';
+import { Base, Session as S } from 'orm';
+import Session from 'session';
+import * as Sessions from 'sessions';
+"#;
+        for adapter in [&JavaScriptAdapter as &dyn LangAdapter, &TypeScriptAdapter] {
+            let captures = run_query(adapter, source, "import(member=/^Session$/)");
+            assert_eq!(captures.len(), 2);
+            assert_eq!(captures[0]["module"], "'./db'");
+            assert_eq!(
+                captures[1]["members"],
+                serde_json::json!(["Base", "Session"])
+            );
+
+            assert!(run_query(adapter, source, "import(member=/^(S|Sessions)$/)").is_empty());
+        }
+    }
+
+    #[test]
+    fn member_chain_matches_full_callee_path() {
+        let source = "a.b.c.get('/x');\nclient\n  .api\n  .get('/y');\nget('/z');\n";
+        let captures = run_query(&JavaScriptAdapter, source, r"call(callee=/^a\.b\.c\.get$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["callee"], "get");
+        assert_eq!(captures[0]["callee_path"], "a.b.c.get");
+
+        let captures = run_query(
+            &JavaScriptAdapter,
+            source,
+            "call(callee=\"client.api.get\")",
+        );
+        assert_eq!(captures.len(), 1);
+        assert_eq!(
+            run_query(&JavaScriptAdapter, source, "call(callee=/^get$/)").len(),
+            3
+        );
+    }
+
+    #[test]
+    fn assignments_cover_declarations_and_members() {
+        let source = "const apiKey = 'k1';\nlet other = 1;\nconfig.apiKey = process.env.KEY;\nfunction apiKeyFor(user) {}\n";
+        let captures = run_query(&JavaScriptAdapter, source, "assign(name=/apiKey/)");
+        assert_eq!(captures.len(), 2);
+        let captures = run_query(
+            &JavaScriptAdapter,
+            source,
+            "assign(name=/apiKey/, value=/env/)",
+        );
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["value"], "process.env.KEY");
+    }
 }
@@ -1,19 +1,26 @@
 /// Language adapters for different programming languages.
+mod common_queries;
 pub mod csharp;
 pub mod go;
 pub mod java;
 pub mod javascript;
+pub mod php;
 pub mod python;
 pub mod rust;
+#[cfg(test)]
+mod test_util;
 
 use crate::adapter::LangAdapter;
 use phf::phf_map;
+use std::io::Read;
+use std::path::Path;
 use std::sync::Arc;
 
 pub use csharp::CSharpAdapter;
 pub use go::GoAdapter;
 pub use java::JavaAdapter;
 pub use javascript::{JavaScriptAdapter, TypeScriptAdapter};
+pub use php::PhpAdapter;
 pub use python::PythonAdapter;
 pub use rust::RustAdapter;
 
@@ -27,22 +34,94 @@ pub enum LangId {
     Rust,
     Go,
     Java,
+    Php,
 }
 
 /// Map file extensions to language IDs.
 pub static LANG_BY_EXT: phf::Map<&'static str, LangId> = phf_map! {
     "js" => LangId::JavaScript,
     "jsx" => LangId::JavaScript,
+    "mjs" => LangId::JavaScript,
+    "cjs" => LangId::JavaScript,
     "ts" => LangId::TypeScript,
     "tsx" => LangId::TypeScript,
+    "mts" => LangId::TypeScript,
+    "cts" => LangId::TypeScript,
     "py" => LangId::Python,
+    "pyi" => LangId::Python,
     "cs" => LangId::CSharp,
     "csx" => LangId::CSharp,
     "rs" => LangId::Rust,
     "go" => LangId::Go,
     "java" => LangId::Java,
+    "php" => LangId::Php,
 };
 
+/// Map multi-part extensions (everything after the first `.` in the file
+/// name) to language IDs. Checked before `LANG_BY_EXT`, since
+/// `Path::extension` only sees the last component (`d.ts` -> `ts`).
+static LANG_BY_COMPOUND_EXT: phf::Map<&'static str, LangId> = phf_map! {
+    "d.ts" => LangId::TypeScript,
+    "d.mts" => LangId::TypeScript,
+    "d.cts" => LangId::TypeScript,
+};
+
+/// Resolve a file's language from its name, honoring compound extensions.
+pub fn lang_for_path(path: &Path) -> Option<LangId> {
+    let file_name = path.file_name()?.to_str()?;
+    let compound = file_name
+        .split_once('.')
+        .and_then(|(_, rest)| LANG_BY_COMPOUND_EXT.get(rest));
+    if let Some(lang_id) = compound {
+        return Some(*lang_id);
+    }
+
+    let ext = path.extension()?.to_str()?;
+    LANG_BY_EXT.get(ext).copied()
+}
+
+/// Map `#!` interpreter names to language IDs. Interpreters without an
+/// adapter (e.g. `bash`, `ruby`) are intentionally absent.
+static LANG_BY_INTERPRETER: phf::Map<&'static str, LangId> = phf_map! {
+    "python" => LangId::Python,
+    "node" => LangId::JavaScript,
+    "nodejs" => LangId::JavaScript,
+    "deno" => LangId::TypeScript,
+    "ts-node" => LangId::TypeScript,
+    "tsx" => LangId::TypeScript,
+    "php" => LangId::Php,
+};
+
+/// Number of leading bytes inspected when sniffing a shebang line.
+const SHEBANG_SAMPLE: usize = 256;
+
+/// Detect a file's language from its `#!` line, reading at most the first
+/// 256 bytes.
+pub fn detect_shebang(path: &Path) -> Option<LangId> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut buf = Vec::with_capacity(SHEBANG_SAMPLE);
+    file.take(SHEBANG_SAMPLE as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+    let first_line = buf.split(|&b| b == b'\n').next()?;
+    lang_from_shebang(std::str::from_utf8(first_line).ok()?)
+}
+
+/// Parse a shebang line such as `#!/usr/bin/env python3`.
+pub fn lang_from_shebang(line: &str) -> Option<LangId> {
+    let rest = line.strip_prefix("#!")?;
+    let mut words = rest.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        // Skip `env` flags such as `-S`.
+        interpreter = words.find(|w| !w.starts_with('-'))?;
+    }
+
+    // Strip version suffixes: python3, python3.11, node18.
+    let base = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    LANG_BY_INTERPRETER.get(base).copied()
+}
+
 /// Get a language adapter by ID.
 pub fn get_adapter(lang_id: LangId) -> Arc<dyn LangAdapter> {
     match lang_id {
@@ -53,24 +132,108 @@ pub fn get_adapter(lang_id: LangId) -> Arc<dyn LangAdapter> {
         LangId::Rust => Arc::new(RustAdapter),
         LangId::Go => Arc::new(GoAdapter),
         LangId::Java => Arc::new(JavaAdapter),
+        LangId::Php => Arc::new(PhpAdapter),
     }
 }
 
 /// Parse a comma-separated language list (e.g., "py,ts,js").
 pub fn parse_lang_list(s: &str) -> Vec<LangId> {
     s.split(',')
-        .filter_map(|part| {
-            let part = part.trim();
-            match part {
-                "py" | "python" => Some(LangId::Python),
-                "js" | "javascript" => Some(LangId::JavaScript),
-                "ts" | "typescript" => Some(LangId::TypeScript),
-                "cs" | "csharp" | "c#" => Some(LangId::CSharp),
-                "rs" | "rust" => Some(LangId::Rust),
-                "go" | "golang" => Some(LangId::Go),
-                "java" => Some(LangId::Java),
-                _ => None,
-            }
-        })
+        .filter_map(|part| lang_by_name(part.trim()))
         .collect()
 }
+
+/// Resolve a language name or short alias, as accepted by `--lang`.
+pub fn lang_by_name(name: &str) -> Option<LangId> {
+    match name {
+        "py" | "python" => Some(LangId::Python),
+        "js" | "javascript" => Some(LangId::JavaScript),
+        "ts" | "typescript" => Some(LangId::TypeScript),
+        "cs" | "csharp" | "c#" => Some(LangId::CSharp),
+        "rs" | "rust" => Some(LangId::Rust),
+        "go" | "golang" => Some(LangId::Go),
+        "java" => Some(LangId::Java),
+        "php" => Some(LangId::Php),
+        _ => None,
+    }
+}
+
+/// Parse an `ext=lang` extension mapping such as `vue=javascript` or
+/// `tsx.snap=ts`. A leading dot on the extension is ignored.
+pub fn parse_ext_mapping(spec: &str) -> anyhow::Result<(String, LangId)> {
+    let Some((ext, lang)) = spec.split_once('=') else {
+        anyhow::bail!("expected ext=lang, e.g. vue=javascript; got {:?}", spec);
+    };
+    let ext = ext.trim().trim_start_matches('.');
+    if ext.is_empty() {
+        anyhow::bail!("missing extension in {:?}", spec);
+    }
+    let lang = lang_by_name(lang.trim()).ok_or_else(|| {
+        anyhow::anyhow!(
+            "unknown language {:?} (expected py, js, ts, cs, rs, go, java, or php)",
+            lang.trim()
+        )
+    })?;
+    Ok((ext.to_string(), lang))
+}
+
+/// The language `extra` maps `path` to: the longest extension the file name
+/// ends with, so `tsx.snap` wins over `snap`.
+pub fn mapped_lang(path: &Path, extra: &[(String, LangId)]) -> Option<LangId> {
+    let file_name = path.file_name()?.to_str()?;
+    extra
+        .iter()
+        .filter(|(ext, _)| {
+            file_name
+                .strip_suffix(ext.as_str())
+                .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+        })
+        .max_by_key(|(ext, _)| ext.len())
+        .map(|&(_, lang)| lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebang_interpreters() {
+        assert_eq!(
+            lang_from_shebang("#!/usr/bin/env python3"),
+            Some(LangId::Python)
+        );
+        assert_eq!(
+            lang_from_shebang("#!/usr/bin/python3.11"),
+            Some(LangId::Python)
+        );
+        assert_eq!(
+            lang_from_shebang("#!/usr/bin/env node"),
+            Some(LangId::JavaScript)
+        );
+        assert_eq!(
+            lang_from_shebang("#!/usr/bin/env -S deno run"),
+            Some(LangId::TypeScript)
+        );
+        assert_eq!(lang_from_shebang("#!/bin/bash"), None);
+        assert_eq!(lang_from_shebang("#!/usr/bin/env ruby"), None);
+        assert_eq!(lang_from_shebang("print('no shebang')"), None);
+    }
+
+    #[test]
+    fn extra_extensions_map_to_existing_adapters() {
+        let cases = [
+            ("server.mjs", LangId::JavaScript),
+            ("config.cjs", LangId::JavaScript),
+            ("module.mts", LangId::TypeScript),
+            ("module.cts", LangId::TypeScript),
+            ("types.d.ts", LangId::TypeScript),
+            ("types.d.mts", LangId::TypeScript),
+            ("foo.pyi", LangId::Python),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(lang_for_path(Path::new(name)), Some(expected), "{name}");
+        }
+        assert_eq!(lang_for_path(Path::new("go.mod")), None);
+        assert_eq!(lang_for_path(Path::new("Makefile")), None);
+    }
+}
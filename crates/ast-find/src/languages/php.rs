@@ -0,0 +1,165 @@
+/// PHP language adapter.
+use crate::adapter::LangAdapter;
+use crate::dsl::{Expr, Kind};
+use anyhow::Result;
+
+pub struct PhpAdapter;
+
+impl LangAdapter for PhpAdapter {
+    fn name(&self) -> &'static str {
+        "php"
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_php::language_php()
+    }
+
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &[
+            "function_definition",
+            "method_declaration",
+            "class_declaration",
+            "interface_declaration",
+            "trait_declaration",
+        ]
+    }
+
+    fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
+        match expr {
+            Expr::Node { kind, .. } => {
+                let query_str = match kind {
+                    Kind::Call => {
+                        r#"
+                        (function_call_expression
+                          function: [(name) (qualified_name)] @callee_id
+                        ) @call
+
+                        (member_call_expression
+                          object: (_) @obj
+                          name: (name) @prop
+                        ) @call
+
+                        (nullsafe_member_call_expression
+                          object: (_) @obj
+                          name: (name) @prop
+                        ) @call
+
+                        (scoped_call_expression
+                          scope: (_) @obj
+                          name: (name) @prop
+                        ) @call
+                        "#
+                    }
+                    Kind::Import => {
+                        r#"
+                        (namespace_use_clause
+                          [(name) (qualified_name)] @module
+                        ) @import
+
+                        (require_expression (_) @module) @import
+                        (require_once_expression (_) @module) @import
+                        (include_expression (_) @module) @import
+                        (include_once_expression (_) @module) @import
+                        "#
+                    }
+                    Kind::Def => {
+                        r#"
+                        (function_definition
+                          name: (name) @name
+                        ) @def
+
+                        (method_declaration
+                          name: (name) @name
+                        ) @def
+
+                        (class_declaration
+                          name: (name) @name
+                        ) @def
+
+                        (interface_declaration
+                          name: (name) @name
+                        ) @def
+
+                        (trait_declaration
+                          name: (name) @name
+                        ) @def
+
+                        (enum_declaration
+                          name: (name) @name
+                        ) @def
+                        "#
+                    }
+                    Kind::Impl | Kind::Decorator | Kind::Assignment => return Ok(Vec::new()),
+                    Kind::Literal => {
+                        r#"
+                        [
+                          (string)
+                          (encapsed_string)
+                          (heredoc)
+                          (nowdoc)
+                          (integer)
+                          (float)
+                        ] @literal
+                        "#
+                    }
+                };
+
+                let lang = self.language();
+                let query = tree_sitter::Query::new(&lang, query_str)?;
+                Ok(vec![query])
+            }
+            _ => anyhow::bail!("Composite expressions are handled in the processor"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::test_util::run_query;
+
+    const SOURCE: &str = r#"<?php
+namespace App\Repo;
+
+use App\Db\Connection;
+require_once 'bootstrap.php';
+
+class UserRepo
+{
+    public function find($db, $id)
+    {
+        $rows = $db->query("SELECT * FROM users WHERE id = ?", [$id]);
+        return array_map(fn ($r) => User::fromRow($r), $rows);
+    }
+}
+"#;
+
+    fn run(query: &str) -> Vec<serde_json::Value> {
+        run_query(&PhpAdapter, SOURCE, query)
+    }
+
+    #[test]
+    fn member_call_matches_prop() {
+        let captures = run("call(prop=/^query$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["callee"], "query");
+        assert_eq!(captures[0]["object"], "$db");
+    }
+
+    #[test]
+    fn function_and_static_calls_match() {
+        assert_eq!(run("call(callee=/^array_map$/)").len(), 1);
+        assert_eq!(run("call(prop=/^fromRow$/)").len(), 1);
+    }
+
+    #[test]
+    fn use_and_require_are_imports() {
+        assert_eq!(run(r"import(module=/Connection/)").len(), 1);
+        assert_eq!(run(r"import(module=/bootstrap/)").len(), 1);
+    }
+
+    #[test]
+    fn class_and_method_are_defs() {
+        assert_eq!(run("def(name=/^(UserRepo|find)$/)").len(), 2);
+    }
+}
@@ -1,6 +1,7 @@
 /// Python language adapter.
-use crate::adapter::LangAdapter;
+use crate::adapter::{CaptureBundle, LangAdapter};
 use crate::dsl::{Expr, Kind};
+use crate::processor::node_text;
 use anyhow::Result;
 
 pub struct PythonAdapter;
@@ -14,6 +15,10 @@ impl LangAdapter for PythonAdapter {
         tree_sitter_python::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &["function_definition", "class_definition"]
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
@@ -38,6 +43,11 @@ impl LangAdapter for PythonAdapter {
                           name: (dotted_name) @module
                         ) @import
 
+                        (import_statement
+                          name: (aliased_import
+                            name: (dotted_name) @module)
+                        ) @import
+
                         (import_from_statement
                           module_name: (dotted_name) @module
                         ) @import
@@ -54,6 +64,40 @@ impl LangAdapter for PythonAdapter {
                         ) @def
                         "#
                     }
+                    Kind::Impl => return Ok(Vec::new()),
+                    Kind::Decorator => {
+                        r#"
+                        (decorator
+                          [(identifier) (attribute)] @name
+                        ) @decorator
+
+                        (decorator
+                          (call
+                            function: (_) @name
+                            arguments: (argument_list) @args
+                          )
+                        ) @decorator
+                        "#
+                    }
+                    Kind::Literal => {
+                        r#"
+                        [(string) (integer) (float)] @literal
+                        "#
+                    }
+                    Kind::Assignment => {
+                        r#"
+                        (assignment
+                          left: (identifier) @name
+                          right: (_) @value
+                        ) @assign
+
+                        (assignment
+                          left: (attribute
+                            attribute: (identifier) @name)
+                          right: (_) @value
+                        ) @assign
+                        "#
+                    }
                 };
 
                 let lang = self.language();
@@ -63,4 +107,180 @@ impl LangAdapter for PythonAdapter {
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    /// Capture the decorated function or class name as `target`, each `as`
+    /// name of an import as `alias`, and each name a `from` import binds as
+    /// `member`.
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        if matches!(node.kind(), "import_statement" | "import_from_statement") {
+            let from = node.kind() == "import_from_statement";
+            let mut cursor = node.walk();
+            for name in node.children_by_field_name("name", &mut cursor) {
+                if let Some(alias) = name.child_by_field_name("alias") {
+                    caps.push("alias", node_text(src, &alias));
+                }
+                let member = name.child_by_field_name("name").unwrap_or(name);
+                if from {
+                    caps.push("member", node_text(src, &member));
+                }
+            }
+            return;
+        }
+        if node.kind() != "decorator" {
+            return;
+        }
+        let name = node
+            .parent()
+            .and_then(|p| p.child_by_field_name("definition"))
+            .and_then(|d| d.child_by_field_name("name"));
+        if let Some(name) = name {
+            caps.insert("target", node_text(src, &name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::test_util::run_query;
+
+    #[test]
+    fn attribute_calls_match_prop_and_callee() {
+        let source = "import requests\n\nresp = requests.get(url)\ndata = cache.get('k')\n";
+        for query in ["call(prop=/^get$/)", "call(callee=/^get$/)"] {
+            let captures = run_query(&PythonAdapter, source, query);
+            assert_eq!(captures.len(), 2, "{query}");
+            assert!(captures.iter().all(|c| c["callee"] == "get"));
+        }
+    }
+
+    #[test]
+    fn argkind_checks_the_nth_argument_node() {
+        let source = "db.execute('SELECT 1')\ncur.execute(query)\nconn.execute(sql, (1,))\n";
+        let objects = |query: &str| -> Vec<String> {
+            let mut objects: Vec<_> = run_query(&PythonAdapter, source, query)
+                .iter()
+                .map(|c| c["object"].as_str().unwrap().to_string())
+                .collect();
+            objects.sort();
+            objects
+        };
+        assert_eq!(objects("call(prop=/^execute$/, argkind=1:string)"), ["db"]);
+        assert_eq!(
+            objects("call(prop=/^execute$/, argkind=1:identifier)"),
+            ["conn", "cur"]
+        );
+        assert_eq!(
+            objects("call(prop=/^execute$/, !argkind=1:string)"),
+            ["conn", "cur"]
+        );
+        assert_eq!(objects("call(prop=/^execute$/, argkind=2:tuple)"), ["conn"]);
+        assert!(objects("call(prop=/^execute$/, argkind=3:identifier)").is_empty());
+    }
+
+    #[test]
+    fn decorators_capture_target() {
+        let source = "@app.route('/users')\ndef list_users():\n    pass\n\n@dataclass\nclass User:\n    pass\n";
+        let captures = run_query(&PythonAdapter, source, "decorator(name=/route$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "app.route");
+        assert_eq!(captures[0]["args"], "('/users')");
+        assert_eq!(captures[0]["target"], "list_users");
+
+        let captures = run_query(&PythonAdapter, source, "decorator(name=/^dataclass$/)");
+        assert_eq!(captures[0]["target"], "User");
+    }
+
+    #[test]
+    fn literal_value_strips_quotes() {
+        let source = "API = 'https://api.example.com'\nNAME = \"x\"\nTIMEOUT = 30\n";
+        let captures = run_query(&PythonAdapter, source, r"literal(value=/^https?:\/\//)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["value"], "https://api.example.com");
+
+        let captures = run_query(&PythonAdapter, source, "literal(value=\"30\")");
+        assert_eq!(captures.len(), 1);
+    }
+
+    #[test]
+    fn imports_capture_aliases() {
+        let source =
+            "import numpy as np\nimport os\nfrom collections import OrderedDict as OD, deque\n";
+        let captures = run_query(&PythonAdapter, source, "import(alias=/^np$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["module"], "numpy");
+        assert_eq!(captures[0]["aliases"], serde_json::json!(["np"]));
+
+        let captures = run_query(&PythonAdapter, source, "import(alias=/^OD$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["module"], "collections");
+
+        assert_eq!(
+            run_query(&PythonAdapter, source, "import(module=/./)").len(),
+            3
+        );
+        assert!(run_query(&PythonAdapter, source, "import(alias=/^os$/)").is_empty());
+    }
+
+    #[test]
+    fn from_imports_capture_members() {
+        let source = "from db import Session
+from db.orm import Base, Session as S
+import Session
+";
+        let captures = run_query(&PythonAdapter, source, "import(member=/^Session$/)");
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0]["module"], "db");
+        assert_eq!(captures[0]["members"], serde_json::json!(["Session"]));
+        assert_eq!(captures[1]["module"], "db.orm");
+        assert_eq!(
+            captures[1]["members"],
+            serde_json::json!(["Base", "Session"])
+        );
+
+        assert!(run_query(&PythonAdapter, source, "import(member=/^S$/)").is_empty());
+        assert_eq!(
+            run_query(&PythonAdapter, source, "import(module=/^Session$/)")[0]["members"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn negated_name_predicate_excludes_matches() {
+        let source = "def test_login():\n    pass\n\ndef login():\n    pass\n\ndef helper_test():\n    pass\n";
+        let captures = run_query(&PythonAdapter, source, "def(!name=/test/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "login");
+
+        let captures = run_query(&PythonAdapter, source, "def(name=/login/, !name=/^test_/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "login");
+    }
+
+    #[test]
+    fn multi_kind_query_finds_def_and_call() {
+        let source = "def init_db():\n    pass\n\ninit_db()\nconnect()\n";
+        let captures = run_query(&PythonAdapter, source, "def|call(text=/init_db/)");
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().any(|c| c["name"] == "init_db"));
+        assert!(captures.iter().any(|c| c["callee"] == "init_db"));
+    }
+
+    #[test]
+    fn assignments_match_target_name_and_value() {
+        let source = "password = \"x\"\nself.db_password = os.environ['PW']\n\ndef set_password(value):\n    user = value\n";
+        let captures = run_query(&PythonAdapter, source, "assign(name=/password/)");
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().all(|c| c["name"] != "set_password"));
+        let plain = captures.iter().find(|c| c["name"] == "password").unwrap();
+        assert_eq!(plain["value"], "\"x\"");
+
+        let captures = run_query(
+            &PythonAdapter,
+            source,
+            "assign(name=/password/, value=/environ/)",
+        );
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "db_password");
+    }
 }
@@ -1,6 +1,7 @@
 /// Rust language adapter.
-use crate::adapter::LangAdapter;
+use crate::adapter::{CaptureBundle, LangAdapter};
 use crate::dsl::{Expr, Kind};
+use crate::processor::node_text;
 use anyhow::Result;
 
 pub struct RustAdapter;
@@ -14,6 +15,10 @@ impl LangAdapter for RustAdapter {
         tree_sitter_rust::language()
     }
 
+    fn enclosing_block_kinds(&self) -> &'static [&'static str] {
+        &["function_item", "impl_item", "trait_item"]
+    }
+
     fn compile(&self, expr: &Expr) -> Result<Vec<tree_sitter::Query>> {
         match expr {
             Expr::Node { kind, .. } => {
@@ -113,6 +118,49 @@ impl LangAdapter for RustAdapter {
                         ) @def
                         "#
                     }
+                    Kind::Impl => {
+                        r#"
+                        (impl_item
+                          trait: (_) @trait
+                          type: (_) @type
+                        ) @impl
+
+                        (impl_item
+                          !trait
+                          type: (_) @type
+                        ) @impl
+                        "#
+                    }
+                    Kind::Decorator => return Ok(Vec::new()),
+                    Kind::Literal => {
+                        r#"
+                        [
+                          (string_literal)
+                          (raw_string_literal)
+                          (integer_literal)
+                          (float_literal)
+                        ] @literal
+                        "#
+                    }
+                    Kind::Assignment => {
+                        r#"
+                        (let_declaration
+                          pattern: (identifier) @name
+                          value: (_) @value
+                        ) @assign
+
+                        (assignment_expression
+                          left: (identifier) @name
+                          right: (_) @value
+                        ) @assign
+
+                        (assignment_expression
+                          left: (field_expression
+                            field: (field_identifier) @name)
+                          right: (_) @value
+                        ) @assign
+                        "#
+                    }
                 };
 
                 let lang = self.language();
@@ -122,4 +170,215 @@ impl LangAdapter for RustAdapter {
             _ => anyhow::bail!("Composite expressions are handled in the processor"),
         }
     }
+
+    /// Capture the `<T: Bound>` list of generic items as `generics`, followed
+    /// by the `where` clause when there is one, so bounds written either way
+    /// can be matched. A `use` declaration instead gets every path its brace
+    /// groups name, fully qualified, as `use_path`, and any `as` names as `alias`.
+    fn enrich_captures(&self, node: tree_sitter::Node, src: &[u8], caps: &mut CaptureBundle) {
+        if node.kind() == "use_declaration" {
+            if let Some(argument) = node.child_by_field_name("argument") {
+                expand_use_tree(argument, "", src, caps);
+            }
+            return;
+        }
+        let Some(params) = node.child_by_field_name("type_parameters") else {
+            return;
+        };
+        let mut generics = node_text(src, &params);
+        let mut cursor = node.walk();
+        if let Some(clause) = node
+            .children(&mut cursor)
+            .find(|c| c.kind() == "where_clause")
+        {
+            generics.push(' ');
+            generics.push_str(&node_text(src, &clause));
+        }
+        caps.insert("generics", generics);
+    }
+}
+
+/// Push the paths a use tree imports, each prefixed with the path of the
+/// brace groups it sits in: `std::{io::{self, Read}, fmt::*}` yields
+/// `std::io`, `std::io::Read`, and `std::fmt::*`.
+fn expand_use_tree(node: tree_sitter::Node, prefix: &str, src: &[u8], caps: &mut CaptureBundle) {
+    let join = |path: &str| match (prefix, path) {
+        ("", path) => path.to_string(),
+        (prefix, "self") => prefix.to_string(),
+        (prefix, path) => format!("{prefix}::{path}"),
+    };
+    // Paths may be split over lines; their text is compared without spaces
+    let path_text = |node: &tree_sitter::Node| {
+        let text = node_text(src, node);
+        text.split_whitespace().collect::<String>()
+    };
+    match node.kind() {
+        "use_list" => {
+            let mut cursor = node.walk();
+            for item in node.named_children(&mut cursor) {
+                expand_use_tree(item, prefix, src, caps);
+            }
+        }
+        "scoped_use_list" => {
+            let prefix = match node.child_by_field_name("path") {
+                Some(path) => join(&path_text(&path)),
+                None => prefix.to_string(),
+            };
+            if let Some(list) = node.child_by_field_name("list") {
+                expand_use_tree(list, &prefix, src, caps);
+            }
+        }
+        "use_as_clause" => {
+            if let Some(path) = node.child_by_field_name("path") {
+                caps.push("use_path", join(&path_text(&path)));
+            }
+            if let Some(alias) = node.child_by_field_name("alias") {
+                caps.push("alias", node_text(src, &alias));
+            }
+        }
+        // Comments between list items
+        kind if kind.ends_with("comment") => {}
+        _ => caps.push("use_path", join(&path_text(&node))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::test_util::run_query;
+
+    const SOURCE: &str = r#"use std::fmt;
+
+struct Foo;
+
+impl Foo {
+    fn new() -> Self {
+        Foo
+    }
+}
+
+impl fmt::Display for Foo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "foo")
+    }
+}
+"#;
+
+    fn run(query: &str) -> Vec<serde_json::Value> {
+        run_query(&RustAdapter, SOURCE, query)
+    }
+
+    #[test]
+    fn trait_impl_exposes_trait_and_type() {
+        let captures = run("impl(trait=/Display$/, type=/^Foo$/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["trait"], "fmt::Display");
+        assert_eq!(captures[0]["type"], "Foo");
+    }
+
+    #[test]
+    fn inherent_impl_has_no_trait() {
+        let captures = run("impl(type=/^Foo$/)");
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().any(|c| c["trait"].is_null()));
+        assert_eq!(
+            run("and(impl(type=/^Foo$/), not(impl(trait=/./)))").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn literal_value_covers_raw_and_byte_strings() {
+        let source = r###"const A: &str = "https://a.example";
+const B: &str = r#"https://b.example"#;
+const C: &[u8] = b"https://c.example";
+const D: &str = "ftp://d.example";
+"###;
+        let captures = run_query(&RustAdapter, source, r"literal(value=/^https?:\/\//)");
+        let mut values: Vec<_> = captures
+            .iter()
+            .filter_map(|c| c["value"].as_str())
+            .collect();
+        values.sort_unstable();
+        assert_eq!(
+            values,
+            [
+                "https://a.example",
+                "https://b.example",
+                "https://c.example"
+            ]
+        );
+    }
+
+    #[test]
+    fn scoped_call_matches_full_callee_path() {
+        let source = "fn main() {\n    let s = std::fs::read_to_string(\"a\");\n    let v = Vec::<u8>::with_capacity(4);\n    read_to_string(\"b\");\n}\n";
+        let captures = run_query(
+            &RustAdapter,
+            source,
+            r"call(callee=/^std::fs::read_to_string$/)",
+        );
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["callee"], "read_to_string");
+        assert_eq!(captures[0]["callee_path"], "std::fs::read_to_string");
+
+        let captures = run_query(
+            &RustAdapter,
+            source,
+            "call(callee=glob:Vec::*::with_capacity)",
+        );
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["callee_path"], "Vec::<u8>::with_capacity");
+    }
+
+    #[test]
+    fn assignments_cover_let_and_field_writes() {
+        let source = "fn main() {\n    let mut token = String::new();\n    token = load();\n    self.token = token;\n    let other = 1;\n}\nfn token() {}\n";
+        let captures = run_query(&RustAdapter, source, "assign(name=/^token$/)");
+        assert_eq!(captures.len(), 3);
+        assert!(captures.iter().any(|c| c["value"] == "String::new()"));
+    }
+
+    #[test]
+    fn generic_bounds_are_matched() {
+        let source = "fn to_json<T: Serialize>(value: &T) -> String { todo!() }\n\
+                      fn dump<W>(out: W) where W: Write + Send {}\n\
+                      struct Wrapper<T>(T);\n\
+                      fn plain() {}\n";
+        let captures = run_query(&RustAdapter, source, "def(generics=/Serialize/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["name"], "to_json");
+        assert_eq!(captures[0]["generics"], "<T: Serialize>");
+
+        let captures = run_query(&RustAdapter, source, "def(generics=/Write/)");
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0]["generics"], "<W> where W: Write + Send");
+
+        assert_eq!(
+            run_query(&RustAdapter, source, "def(generics=/./)").len(),
+            3
+        );
+        assert!(run_query(&RustAdapter, source, "def(name=/^plain$/, generics=/./)").is_empty());
+    }
+
+    #[test]
+    fn braced_use_paths_are_expanded() {
+        let source = "use std::collections::{HashMap, BTreeMap};\n\
+                      use std::{io::{self, Read as R}, fmt::*};\n\
+                      use serde::Serialize;\n";
+        let count = |query: &str| run_query(&RustAdapter, source, query).len();
+        assert_eq!(count("import(module=/std::collections::BTreeMap/)"), 1);
+        assert_eq!(count("import(module=/^std::io$/)"), 1);
+        assert_eq!(count("import(module=/^std::io::Read$/)"), 1);
+        assert_eq!(count("import(module=/^std::fmt::\\*$/)"), 1);
+        assert_eq!(count("import(alias=/^R$/)"), 1);
+        assert_eq!(count("import(module=/^serde::Serialize$/)"), 1);
+        assert_eq!(count("import(module=/^std::collections::Vec$/)"), 0);
+
+        let captures = run_query(&RustAdapter, source, "import(module=/Read/)");
+        assert_eq!(
+            captures[0]["raw"]["use_path"],
+            serde_json::json!(["std::io", "std::io::Read", "std::fmt::*"])
+        );
+    }
 }
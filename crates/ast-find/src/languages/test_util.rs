@@ -0,0 +1,22 @@
+//! Helpers shared by the adapter tests.
+use crate::adapter::LangAdapter;
+use crate::dsl::parse_query;
+use crate::processor::{process_file, ProcessOptions};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+/// The `capture` of every match `query` finds in `source`, read by `adapter`.
+pub(crate) fn run_query(
+    adapter: &dyn LangAdapter,
+    source: &str,
+    query: &str,
+) -> Vec<serde_json::Value> {
+    let mut temp = NamedTempFile::new().expect("create temp file");
+    temp.write_all(source.as_bytes()).expect("write temp file");
+    let expr = parse_query(query).expect("parse query");
+    process_file(adapter, temp.path(), &expr, &ProcessOptions::default())
+        .expect("process file")
+        .into_iter()
+        .map(|record| record.capture)
+        .collect()
+}
@@ -0,0 +1,166 @@
+//! Structure-aware code search as a library. The `ast-find` binary is a CLI
+//! over these modules; embedders call `search` directly.
+//!
+//! ```
+//! use ast_find::{search, SearchOptions};
+//! use std::path::Path;
+//!
+//! let roots = [Path::new("src/test_source/golden")];
+//! let matches = search(&roots, "def(name=/^load$/)", &SearchOptions::default()).unwrap();
+//! assert!(matches.iter().any(|m| m.path.ends_with("sample.py")));
+//! assert!(matches.iter().all(|m| m.capture["name"] == "load"));
+//! ```
+pub mod adapter;
+pub mod dsl;
+#[cfg(test)]
+mod golden_tests;
+pub mod human;
+pub mod languages;
+pub mod processor;
+pub mod sarif;
+pub mod walk;
+
+pub use agent_tools_common::Event;
+pub use processor::{FileMatches, MatchRecord, ProcessOptions};
+pub use walk::WalkOptions;
+
+use anyhow::{Context, Result};
+use languages::{get_adapter, LangId};
+use processor::QueryCache;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Options for `search`.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Which files under the roots are searched.
+    pub walk: WalkOptions,
+    /// How each file is matched and excerpted.
+    pub process: ProcessOptions,
+    /// Stop collecting after this many matches.
+    pub max_results: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            walk: WalkOptions::default(),
+            process: ProcessOptions::default(),
+            max_results: usize::MAX,
+        }
+    }
+}
+
+/// Run `query` over every searchable file under `roots` and return the
+/// matches sorted by path and position, as the CLI emits them. The first
+/// file that cannot be read or queried fails the whole search; use
+/// `search_files` to see per-file errors and skipped files instead.
+pub fn search(roots: &[&Path], query: &str, opts: &SearchOptions) -> Result<Vec<MatchRecord>> {
+    let expr = dsl::parse_query(query)?;
+    let files = walk::collect_files(roots, &opts.walk)?;
+
+    let mut records = Vec::new();
+    for file in search_files(&files, &expr, &opts.process, true) {
+        let matches = file
+            .matches
+            .with_context(|| format!("search {}", file.path.display()))?;
+        records.extend(matches.records);
+    }
+    Ok(sort_matches(records, opts.max_results))
+}
+
+/// One file searched by `search_files`: its matches, or why it could not be
+/// searched.
+pub struct FileResult {
+    pub path: PathBuf,
+    pub matches: Result<FileMatches>,
+}
+
+/// Run `expr` over `files` in parallel on the current rayon pool, returning
+/// a result per file in the order given. Under `fail_fast`, files not yet
+/// started once one has failed are left out.
+pub fn search_files(
+    files: &[(PathBuf, LangId)],
+    expr: &dsl::Expr,
+    opts: &ProcessOptions,
+    fail_fast: bool,
+) -> Vec<FileResult> {
+    let queries = QueryCache::new();
+    let failed = AtomicBool::new(false);
+    files
+        .par_iter()
+        .filter_map(|(path, lang_id)| {
+            if fail_fast && failed.load(Ordering::Relaxed) {
+                return None;
+            }
+            let adapter = get_adapter(*lang_id);
+            let matches =
+                processor::process_file_cached(adapter.as_ref(), path, expr, opts, &queries);
+            if matches.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+            Some(FileResult {
+                path: path.clone(),
+                matches,
+            })
+        })
+        .collect()
+}
+
+/// Sort `records` by path and position, drop repeats of the same span, and
+/// keep the first `max_results`.
+pub fn sort_matches(
+    records: impl IntoIterator<Item = MatchRecord>,
+    max_results: usize,
+) -> Vec<MatchRecord> {
+    let mut matches = BTreeMap::new();
+    for record in records {
+        matches.entry(match_key(&record)).or_insert(record);
+    }
+    matches.into_values().take(max_results).collect()
+}
+
+/// The order matches are emitted in: path, then line, then byte span.
+pub fn match_key(record: &MatchRecord) -> (String, u32, usize, usize) {
+    (
+        record.path.clone(),
+        record.start_line,
+        record.start_byte,
+        record.end_byte,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_spans_languages_and_honors_options() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("a.py"), "fetch()\nfetch()\n").unwrap();
+        std::fs::write(dir.path().join("b.js"), "fetch();\n").unwrap();
+        let roots = [dir.path()];
+
+        let matches = search(&roots, "call(callee=/^fetch$/)", &SearchOptions::default()).unwrap();
+        let found: Vec<_> = matches
+            .iter()
+            .map(|m| (m.lang.as_deref().unwrap(), m.start_line))
+            .collect();
+        assert_eq!(found, [("python", 1), ("python", 2), ("javascript", 1)]);
+
+        let opts = SearchOptions {
+            walk: WalkOptions {
+                lang_filter: Some(languages::parse_lang_list("js")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let matches = search(&roots, "call(callee=/^fetch$/)", &opts).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].path.ends_with("b.js"));
+
+        assert!(search(&roots, "call(", &SearchOptions::default()).is_err());
+    }
+}
@@ -1,115 +1,1126 @@
-mod adapter;
-mod dsl;
-mod languages;
-mod processor;
-
-use agent_tools_common::{write_ndjson, Event};
-use anyhow::Result;
+use agent_tools_common::{write_ndjson_to, write_schema_to, AtomicFile, ErrorCode, Event};
+use anyhow::{Context, Result};
+use ast_find::human::HumanWriter;
+use ast_find::languages::{get_adapter, parse_ext_mapping, parse_lang_list, LangId};
+use ast_find::processor::{self, ChunkIdMode, ContextMode, ProcessOptions};
+use ast_find::walk::WalkOptions;
+use ast_find::{dsl, sarif, walk};
 use clap::Parser;
-use ignore::WalkBuilder;
-use languages::{get_adapter, parse_lang_list, LangId, LANG_BY_EXT};
-use rayon::prelude::*;
+use serde::Serialize;
 use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
-#[command(name = "ast-find", about = "Structure-aware repository search")]
+#[command(
+    name = "ast-find",
+    version,
+    about = "Structure-aware repository search"
+)]
 struct Opt {
-    /// Directory to search (repo root)
+    /// Directory to search (repo root); repeat to search several
     #[arg(long, default_value = ".")]
-    within: String,
+    within: Vec<String>,
     /// Comma-separated language list (e.g., py,ts,js)
     #[arg(long)]
     lang: Option<String>,
     /// DSL query, e.g., call(callee=/^axios\.(get|post)$/)
     #[arg(long, default_value = "call(callee=/^foo$/)")]
     query: String,
-    /// Lines of context to include in excerpts
+    /// Raw tree-sitter query for the single `--lang`, used instead of the DSL;
+    /// its `@match` capture marks each result node
+    #[arg(
+        long,
+        value_name = "QUERY",
+        conflicts_with = "query",
+        requires = "lang"
+    )]
+    raw_query: Option<String>,
+    /// Lines of context to include in excerpts, before and after the match
     #[arg(long, default_value_t = 2)]
     context: u32,
-    /// Maximum number of results
+    /// Lines of context before the match (overrides --context on that side)
+    #[arg(long, value_name = "LINES")]
+    context_before: Option<u32>,
+    /// Lines of context after the match (overrides --context on that side)
+    #[arg(long, value_name = "LINES")]
+    context_after: Option<u32>,
+    /// Excerpt unit: `lines` (use --context) or `block` (whole enclosing function/class)
+    #[arg(long, value_enum, default_value_t = ContextMode::Lines)]
+    context_mode: ContextMode,
+    /// Skip files whose average line is longer than this many bytes (minified
+    /// code) or that carry an @generated / DO NOT EDIT banner (0 = search them)
+    #[arg(long, default_value_t = 500)]
+    max_line_length: usize,
+    /// Skip files larger than this (e.g. 10MB, 512KiB; 0 = no limit), and
+    /// never read more than this much of a file
+    #[arg(long, value_name = "SIZE", default_value = "10MB", value_parser = parse_file_size)]
+    max_filesize: usize,
+    /// Truncate excerpts longer than this many bytes (at a UTF-8 boundary)
+    #[arg(long)]
+    max_excerpt_bytes: Option<usize>,
+    /// Use exactly the matched source as the excerpt (overrides --context and --context-mode)
+    #[arg(long, default_value_t = false)]
+    only_matching: bool,
+    /// Drop matches starting before this line
+    #[arg(long)]
+    after: Option<u32>,
+    /// Drop matches starting after this line
+    #[arg(long)]
+    before: Option<u32>,
+    /// Drop matches scoring below this: each regex predicate a match satisfies
+    /// adds 1, a glob 1.5, a literal or argkind 2, a negation 0.5
+    #[arg(long, value_name = "SCORE")]
+    min_score: Option<f32>,
+    /// Maximum number of results (under --count-by, of tallies; every match
+    /// is still counted)
     #[arg(long, default_value_t = 5000)]
     max_results: usize,
+    /// How chunk ids are derived: `position` (path + lines) or `content` (path + matched text)
+    #[arg(long, value_enum, default_value_t = ChunkIdMode::Position)]
+    chunk_id: ChunkIdMode,
+    /// Stop at the first file that fails to parse and exit non-zero
+    #[arg(long, default_value_t = false)]
+    fail_fast: bool,
+    /// Detect the language of extensionless files from their `#!` line
+    #[arg(long, default_value_t = false)]
+    shebang: bool,
+    /// Directory levels to descend below --within (0 = only its own files)
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Only search files changed since this git revision (per `git diff`),
+    /// e.g. origin/main in CI
+    #[arg(long, value_name = "REF")]
+    changed_since: Option<String>,
+    /// Search the files listed one per line in PATH (`-` for stdin) instead of
+    /// walking --within; files with no known language are skipped with a
+    /// note on stderr
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["within", "max_depth", "changed_since"])]
+    files_from: Option<PathBuf>,
+    /// Search files with this extension as the given language, e.g.
+    /// vue=javascript or tsx.snap=ts (repeatable; overrides built-in mappings)
+    #[arg(long, value_name = "EXT=LANG", value_parser = parse_ext_mapping)]
+    map_ext: Vec<(String, LangId)>,
+    /// Search files excluded by .gitignore, .ignore, and global git excludes
+    #[arg(long, default_value_t = false)]
+    no_ignore: bool,
+    /// Search hidden files and directories (the default)
+    #[arg(long, default_value_t = false, overrides_with = "no_hidden")]
+    hidden: bool,
+    /// Skip hidden files and directories
+    #[arg(long, default_value_t = false, overrides_with = "hidden")]
+    no_hidden: bool,
+    /// Print the files that would be searched (path + language) and exit
+    #[arg(long, default_value_t = false)]
+    list_files: bool,
+    /// Worker threads for file processing (0 = one per logical core)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    /// Instead of match events, emit `{value, count}` tallies of this capture
+    /// field (e.g. callee, module, name), most frequent first
+    #[arg(long, value_parser = parse_count_by)]
+    count_by: Option<String>,
+    /// Instead of match events, emit `{from, to, path, line}` call-graph
+    /// edges from the enclosing function to each matched callee
+    #[arg(long, default_value_t = false, conflicts_with_all = ["count_by", "fields"])]
+    edges: bool,
+    /// Comma-separated match fields to emit, e.g. path,start_line,callee
+    /// (capture keys may be given bare or as capture.<key>)
+    #[arg(long, value_parser = parse_fields, conflicts_with = "count_by")]
+    fields: Option<Fields>,
+    /// Hoist the non-null `capture` keys of each match to top-level
+    /// `cap_<key>` fields and drop the nested object, for flat-table tools
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["fields", "count_by", "edges"]
+    )]
+    flatten_capture: bool,
+    /// Output format: `ndjson` events, or one SARIF 2.1.0 log of every match
+    /// for code-scanning tools
+    #[arg(long, value_enum, default_value_t = OutputFormat::Ndjson)]
+    format: OutputFormat,
+    /// Emit a trailing summary event with per-file parse+query times, slowest first
+    #[arg(long, default_value_t = false)]
+    timings: bool,
+    /// Write events to this file instead of stdout; it appears only once the
+    /// search has finished successfully
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Print the JSON Schema of the NDJSON events and exit
+    #[arg(long, default_value_t = false)]
+    print_schema: bool,
+    /// Color `--format human` output: stdout is a terminal and `NO_COLOR` was
+    /// not set by the caller. Decided in `main`, not a flag.
+    #[arg(skip)]
+    color: bool,
+}
+
+/// What `run` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// One JSON event per line.
+    Ndjson,
+    /// A single SARIF 2.1.0 document.
+    Sarif,
+    /// `path:line:` headers and numbered excerpts for reading in a terminal.
+    Human,
 }
 
 fn main() -> Result<()> {
+    // Read before it is overridden below
+    let no_color = std::env::var_os("NO_COLOR").is_some();
+
     // Deterministic environment
     std::env::set_var("NO_COLOR", "1");
     std::env::set_var("TZ", "UTC");
 
-    let opt = Opt::parse();
+    let mut opt = Opt::parse();
 
-    // Parse DSL query
-    let expr = dsl::parse_query(&opt.query)?;
+    let stdout = std::io::stdout();
+    opt.color = !no_color && opt.output.is_none() && stdout.is_terminal();
+    if opt.print_schema {
+        return write_schema_to(&mut stdout.lock());
+    }
+    match &opt.output {
+        Some(path) => {
+            let mut file = AtomicFile::create(path)?;
+            run(&opt, &mut file)?;
+            file.commit()
+        }
+        None => run(&opt, &mut stdout.lock()),
+    }
+}
+
+/// One `--count-by` line.
+#[derive(Serialize)]
+struct CountRecord {
+    value: String,
+    count: usize,
+}
+
+/// One `--edges` line: `from` calls `to` at `path:line`.
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct EdgeRecord {
+    path: String,
+    line: u32,
+    from: String,
+    to: String,
+}
 
-    // Parse language filter
-    let lang_filter: Option<Vec<LangId>> = opt.lang.as_ref().map(|s| parse_lang_list(s));
+/// Bucket for matches whose counted capture is null.
+const NONE_BUCKET: &str = "<none>";
+
+/// One `--list-files` line.
+#[derive(Serialize)]
+struct ListedFile {
+    path: String,
+    lang: &'static str,
+}
+
+/// Search every matching file under `--within`, writing NDJSON events sorted
+/// by path and line.
+fn run<W: Write>(opt: &Opt, out: &mut W) -> Result<()> {
+    // Parse DSL query, or validate the raw one against its language
+    let expr = match &opt.raw_query {
+        Some(raw) => {
+            let langs = parse_lang_list(opt.lang.as_deref().unwrap_or_default());
+            let [lang] = langs[..] else {
+                anyhow::bail!("--raw-query is written for one grammar; pass exactly one --lang");
+            };
+            processor::compile_raw(get_adapter(lang).as_ref(), raw)?;
+            dsl::Expr::Raw(raw.clone())
+        }
+        None => dsl::parse_query(&opt.query)?,
+    };
+    let sarif = opt.format == OutputFormat::Sarif;
+    let human = opt.format == OutputFormat::Human;
+    if (sarif || human)
+        && (opt.count_by.is_some()
+            || opt.edges
+            || opt.fields.is_some()
+            || opt.flatten_capture
+            || opt.list_files)
+    {
+        anyhow::bail!("--format sarif and --format human report matches; they cannot be combined with --count-by, --edges, --fields, --flatten-capture, or --list-files");
+    }
 
     // Walk the directory and collect files
-    let mut files = Vec::new();
-    let walker = WalkBuilder::new(&opt.within)
-        .hidden(false)
-        .git_ignore(true)
-        .build();
-
-    for entry in walker {
-        let entry = entry?;
-        if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-            if let Some(ext) = entry.path().extension() {
-                if let Some(lang_id) = LANG_BY_EXT.get(ext.to_str().unwrap_or("")) {
-                    // Apply language filter
-                    if let Some(ref filter) = lang_filter {
-                        if !filter.contains(lang_id) {
-                            continue;
-                        }
-                    }
-                    files.push((entry.path().to_path_buf(), *lang_id));
-                }
+    let walk_opts = WalkOptions {
+        lang_filter: opt.lang.as_ref().map(|s| parse_lang_list(s)),
+        shebang: opt.shebang,
+        max_depth: opt.max_depth,
+        no_ignore: opt.no_ignore,
+        skip_hidden: opt.no_hidden,
+        changed_since: opt.changed_since.clone(),
+        extensions: opt.map_ext.clone(),
+    };
+    let files = match &opt.files_from {
+        Some(list) => {
+            let (files, unknown) = walk::classify_files(read_file_list(list)?, &walk_opts);
+            for path in unknown {
+                eprintln!(
+                    "ast-find: skipping {}: no language for this file type",
+                    path.display()
+                );
             }
+            files
+        }
+        None => {
+            let roots: Vec<&Path> = opt.within.iter().map(Path::new).collect();
+            walk::collect_files(&roots, &walk_opts)?
+        }
+    };
+
+    if opt.list_files {
+        for (path, lang_id) in &files {
+            let file = ListedFile {
+                path: path.to_string_lossy().to_string(),
+                lang: get_adapter(*lang_id).name(),
+            };
+            write_ndjson_to(out, &file)?;
         }
+        return Ok(());
     }
 
-    // Sort files for deterministic output
-    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let process_opts = ProcessOptions {
+        context_before: opt.context_before.unwrap_or(opt.context),
+        context_after: opt.context_after.unwrap_or(opt.context),
+        context_mode: opt.context_mode,
+        chunk_id: opt.chunk_id,
+        max_excerpt_bytes: opt.max_excerpt_bytes,
+        only_matching: opt.only_matching,
+        max_line_length: (opt.max_line_length > 0).then_some(opt.max_line_length),
+        max_file_size: (opt.max_filesize > 0).then_some(opt.max_filesize),
+    };
 
-    // Process files in parallel and collect results
-    let results = Arc::new(Mutex::new(BTreeMap::new()));
-    let max_results = opt.max_results;
-    let context = opt.context;
+    // A dedicated pool so --threads caps CPU use; 0 lets rayon pick
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(opt.threads)
+        .build()?;
+    let per_file =
+        pool.install(|| ast_find::search_files(&files, &expr, &process_opts, opt.fail_fast));
 
-    files.par_iter().for_each(|(path, lang_id)| {
-        let adapter = get_adapter(*lang_id);
-        match processor::process_file(adapter.as_ref(), path, &expr, context) {
-            Ok(records) => {
-                let mut results = results.lock().unwrap();
-                for record in records {
-                    if results.len() >= max_results {
-                        return;
-                    }
-                    let key = (record.path.clone(), record.start_line);
-                    if results.contains_key(&key) {
-                        continue;
-                    }
-                    results.insert(key, record.into());
+    // Match and error events, keyed in output order
+    let mut results = BTreeMap::new();
+    let mut records = Vec::new();
+    let mut timings = Vec::new();
+    let mut failed = false;
+    for file in per_file {
+        match file.matches {
+            Ok(matches) => {
+                if let Some(reason) = &matches.skipped {
+                    eprintln!("ast-find: skipping {}: {}", file.path.display(), reason);
                 }
+                if opt.timings {
+                    timings.push((file.path, matches.elapsed, matches.parse_had_errors));
+                }
+                records.extend(matches.records.into_iter().filter(|record| {
+                    in_line_range(opt, record.start_line)
+                        && !opt.min_score.is_some_and(|min| record.score < min)
+                }));
             }
             Err(e) => {
+                failed = true;
+                let path = file.path.to_string_lossy().to_string();
                 let err_event = Event::Error {
-                    code: "E_PARSE".to_string(),
+                    code: ErrorCode::Parse,
                     message: format!("{:#}", e),
-                    path_or_url: Some(path.to_string_lossy().to_string()),
+                    path_or_url: Some(path.clone()),
                 };
-                let mut results = results.lock().unwrap();
-                results.insert((path.to_string_lossy().to_string(), 0), err_event);
+                results.insert((path, 0, 0, 0), err_event);
             }
         }
-    });
+    }
+
+    // Tallies count every match; the limit applies to the rows they produce
+    let limit = match opt.count_by {
+        Some(_) => usize::MAX,
+        None => opt.max_results,
+    };
+    let records = ast_find::sort_matches(records, limit);
+    let mut edges: Vec<EdgeRecord> = if opt.edges {
+        records.iter().filter_map(edge).collect()
+    } else {
+        Vec::new()
+    };
+    edges.sort();
+    // Under --format human, the records behind each match event
+    let mut human_records = BTreeMap::new();
+    for record in records {
+        let key = ast_find::match_key(&record);
+        if human {
+            human_records.insert(key.clone(), record.clone());
+        }
+        results.insert(key, record.into());
+    }
 
     // Output results in sorted order
-    let results = results.lock().unwrap();
-    for (_, event) in results.iter() {
-        write_ndjson(event)?;
+    match &opt.count_by {
+        _ if sarif => {
+            let query = opt.raw_query.as_deref().unwrap_or(&opt.query);
+            let log = sarif::document(query, results.values());
+            serde_json::to_writer_pretty(&mut *out, &log)?;
+            out.write_all(b"\n")?;
+        }
+        _ if human => {
+            let mut writer = HumanWriter::new(opt.color);
+            for (key, event) in results.iter() {
+                match event {
+                    Event::Error {
+                        message,
+                        path_or_url,
+                        ..
+                    } => writer.write_error(out, path_or_url.as_deref(), message)?,
+                    _ => writer.write_match(out, &human_records[key])?,
+                }
+            }
+        }
+        _ if opt.edges => {
+            for event in results.values() {
+                if let Event::Error { .. } = event {
+                    write_ndjson_to(out, event)?;
+                }
+            }
+            for edge in &edges {
+                write_ndjson_to(out, edge)?;
+            }
+        }
+        Some(field) => {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for event in results.values() {
+                match event {
+                    Event::Match { capture, .. } => {
+                        for value in capture_values(&capture[field.as_str()]) {
+                            *counts.entry(value).or_default() += 1;
+                        }
+                    }
+                    _ => write_ndjson_to(out, event)?,
+                }
+            }
+            let mut counts: Vec<_> = counts.into_iter().collect();
+            // Most frequent first; BTreeMap order breaks ties
+            counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+            for (value, count) in counts.into_iter().take(opt.max_results) {
+                write_ndjson_to(out, &CountRecord { value, count })?;
+            }
+        }
+        None => {
+            for event in results.values() {
+                match (&opt.fields, event) {
+                    (Some(fields), Event::Match { .. }) => {
+                        write_ndjson_to(out, &project(event, fields)?)?
+                    }
+                    (None, Event::Match { .. }) if opt.flatten_capture => {
+                        write_ndjson_to(out, &flatten_capture(event)?)?
+                    }
+                    _ => write_ndjson_to(out, event)?,
+                }
+            }
+        }
+    }
+
+    if opt.timings && opt.format == OutputFormat::Ndjson {
+        write_ndjson_to(out, &timings_summary(timings))?;
+    }
+
+    if opt.fail_fast && failed {
+        anyhow::bail!("stopped after the first parse error (--fail-fast)");
     }
 
     Ok(())
 }
+
+/// The call-graph edge for a match: its enclosing function to its callee.
+/// Matches outside any named function or without a callee give none.
+fn edge(record: &processor::MatchRecord) -> Option<EdgeRecord> {
+    Some(EdgeRecord {
+        path: record.path.clone(),
+        line: record.start_line,
+        from: record.enclosing.clone()?,
+        to: record.capture["callee"].as_str()?.to_string(),
+    })
+}
+
+/// Parse `--max-filesize`; KB/MB/GB are powers of 1024, as are KiB/MiB/GiB.
+fn parse_file_size(s: &str) -> Result<usize> {
+    agent_tools_common::parse_size(s, false)
+}
+
+/// The paths listed one per line in `list`, or on stdin for `-`.
+fn read_file_list(list: &Path) -> Result<Vec<PathBuf>> {
+    let text = if list == Path::new("-") {
+        io::read_to_string(io::stdin().lock())?
+    } else {
+        std::fs::read_to_string(list)
+            .with_context(|| format!("cannot read --files-from {}", list.display()))?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether a match starting at `line` lies within `--after`/`--before`
+/// (both inclusive).
+fn in_line_range(opt: &Opt, line: u32) -> bool {
+    opt.after.is_none_or(|after| line >= after) && opt.before.is_none_or(|before| line <= before)
+}
+
+/// Top-level `match` event fields `--fields` can select.
+const MATCH_FIELDS: &[&str] = &[
+    "lang",
+    "path",
+    "start_line",
+    "end_line",
+    "chunk_id",
+    "score",
+    "excerpt",
+    "enclosing",
+    "capture",
+];
+
+/// Field names accepted by `--fields`, validated at startup.
+type Fields = Vec<String>;
+
+fn parse_fields(spec: &str) -> Result<Fields> {
+    let fields: Fields = spec
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(str::to_string)
+        .collect();
+    if fields.is_empty() {
+        anyhow::bail!("expected at least one field");
+    }
+    for field in &fields {
+        if field == "type" {
+            anyhow::bail!("`type` is always emitted; use capture.type for the matched type name");
+        }
+        let capture_key = field.strip_prefix("capture.").unwrap_or(field);
+        if !MATCH_FIELDS.contains(&field.as_str())
+            && !processor::CAPTURE_FIELDS.contains(&capture_key)
+        {
+            anyhow::bail!(
+                "unknown field {:?} (expected one of {} or a capture key: {})",
+                field,
+                MATCH_FIELDS.join(", "),
+                processor::CAPTURE_FIELDS.join(", ")
+            );
+        }
+    }
+    Ok(fields)
+}
+
+/// Parse `--count-by`, which names one of the capture keys.
+fn parse_count_by(field: &str) -> Result<String> {
+    if !processor::CAPTURE_FIELDS.contains(&field) {
+        anyhow::bail!(
+            "unknown capture key {:?} (expected one of {})",
+            field,
+            processor::CAPTURE_FIELDS.join(", ")
+        );
+    }
+    Ok(field.to_string())
+}
+
+/// Reduce a match event to `type` plus the `--fields` keys. A field that is
+/// not a top-level match field is looked up in `capture`.
+fn project(event: &Event, fields: &[String]) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let value = serde_json::to_value(event)?;
+    let mut projected = serde_json::Map::new();
+    projected.insert("type".to_string(), value["type"].clone());
+    for field in fields {
+        let selected = if MATCH_FIELDS.contains(&field.as_str()) {
+            &value[field.as_str()]
+        } else {
+            &value["capture"][field.strip_prefix("capture.").unwrap_or(field)]
+        };
+        projected.insert(field.clone(), selected.clone());
+    }
+    Ok(projected)
+}
+
+/// Prefix of the top-level keys `--flatten-capture` hoists capture fields to.
+/// No `match` event field starts with it, so hoisted keys never collide.
+const FLATTENED_PREFIX: &str = "cap_";
+
+/// A match event with each non-null `capture` key moved to the top level as
+/// `cap_<key>` and the nested object removed. Empty objects (`raw` of an
+/// adapter without extra captures, `groups`) count as null.
+fn flatten_capture(event: &Event) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let serde_json::Value::Object(mut flat) = serde_json::to_value(event)? else {
+        anyhow::bail!("match event did not serialize to an object");
+    };
+    if let Some(serde_json::Value::Object(capture)) = flat.remove("capture") {
+        for (key, value) in capture {
+            let empty = match &value {
+                serde_json::Value::Null => true,
+                serde_json::Value::Object(map) => map.is_empty(),
+                _ => false,
+            };
+            if !empty {
+                flat.insert(format!("{FLATTENED_PREFIX}{key}"), value);
+            }
+        }
+    }
+    Ok(flat)
+}
+
+/// The `--count-by` keys a capture value contributes: one per list element,
+/// the string itself, or [`NONE_BUCKET`] when absent.
+fn capture_values(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::Null => vec![NONE_BUCKET.to_string()],
+        serde_json::Value::String(s) => vec![s.clone()],
+        serde_json::Value::Array(items) => items.iter().flat_map(capture_values).collect(),
+        other => vec![other.to_string()],
+    }
+}
+
+/// Path, parse+query time, and whether the tree had syntax errors.
+type FileTiming = (PathBuf, Duration, bool);
+
+/// The `--timings` summary: every processed file with its parse+query time in
+/// milliseconds, slowest first.
+fn timings_summary(mut timings: Vec<FileTiming>) -> Event {
+    timings.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let files: Vec<_> = timings
+        .iter()
+        .map(|(path, elapsed, parse_had_errors)| {
+            serde_json::json!({
+                "path": path.to_string_lossy(),
+                "ms": elapsed.as_secs_f64() * 1000.0,
+                "parse_had_errors": parse_had_errors,
+            })
+        })
+        .collect();
+    Event::Summary {
+        tool: "ast-find".to_string(),
+        message: format!("parse+query time for {} files", files.len()),
+        stats: serde_json::json!({ "timings": files }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_args(args: &[&str]) -> String {
+        let opt = Opt::parse_from(std::iter::once("ast-find").chain(args.iter().copied()));
+        let mut out = Vec::new();
+        run(&opt, &mut out).expect("run");
+        String::from_utf8(out).expect("utf8 output")
+    }
+
+    fn paths_of(output: &str) -> Vec<String> {
+        let mut paths: Vec<String> = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("valid json"))
+            .map(|event| event["path"].as_str().unwrap().to_string())
+            .collect();
+        paths.dedup();
+        paths
+    }
+
+    #[test]
+    fn list_files_previews_searched_files() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("a.py"), "fetch()\n").unwrap();
+        std::fs::write(dir.path().join("b.js"), "fetch();\n").unwrap();
+        std::fs::write(dir.path().join("c.rs"), "fn main() { fetch(); }\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "fetch()\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+        let common = [
+            "--within",
+            within,
+            "--lang",
+            "py,js",
+            "--query",
+            "call(callee=/^fetch$/)",
+        ];
+
+        let listed = run_args(&[&common[..], &["--list-files"]].concat());
+        let matched = run_args(&common);
+        assert_eq!(paths_of(&listed).len(), 2);
+        assert_eq!(paths_of(&listed), paths_of(&matched));
+        assert!(listed.contains(r#""lang":"python""#));
+    }
+
+    #[test]
+    fn files_from_searches_only_the_listed_files() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("a.py"), "fetch()\n").unwrap();
+        std::fs::write(dir.path().join("b.js"), "fetch();\n").unwrap();
+        std::fs::write(dir.path().join("unlisted.py"), "fetch()\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "fetch()\n").unwrap();
+        let [a, b, notes] = ["a.py", "b.js", "notes.txt"].map(|name| dir.path().join(name));
+        let list = dir.path().join("files.txt");
+        std::fs::write(
+            &list,
+            format!("{}\n{}\n\n{}\n", b.display(), notes.display(), a.display()),
+        )
+        .unwrap();
+
+        let output = run_args(&[
+            "--files-from",
+            list.to_str().unwrap(),
+            "--query",
+            "call(callee=/^fetch$/)",
+        ]);
+        assert_eq!(
+            paths_of(&output),
+            [a.to_str().unwrap(), b.to_str().unwrap()]
+        );
+
+        let (files, unknown) = walk::classify_files([notes.clone(), a], &WalkOptions::default());
+        assert_eq!(files.len(), 1);
+        assert_eq!(unknown, [notes]);
+    }
+
+    #[test]
+    fn output_is_identical_across_thread_counts() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        for i in 0..24 {
+            let source =
+                format!("import os\n\ndef handler_{i}():\n    os.getcwd()\n    fetch({i})\n");
+            std::fs::write(dir.path().join(format!("mod_{i}.py")), source).expect("write file");
+        }
+        let within = dir.path().to_str().unwrap();
+        let query = "or(call(callee=/^(fetch|getcwd)$/), def(name=/^handler_/))";
+
+        let single = run_args(&["--within", within, "--query", query, "--threads", "1"]);
+        let multi = run_args(&["--within", within, "--query", query, "--threads", "4"]);
+        assert_eq!(single.lines().count(), 24 * 3);
+        assert_eq!(single, multi);
+
+        // The limit keeps the first matches in output order, not whichever
+        // files finished first
+        let limited = run_args(&[
+            "--within",
+            within,
+            "--query",
+            query,
+            "--threads",
+            "4",
+            "--max-results",
+            "5",
+        ]);
+        let first: Vec<_> = single.lines().take(5).collect();
+        assert_eq!(limited.lines().collect::<Vec<_>>(), first);
+    }
+
+    #[test]
+    fn matches_name_their_enclosing_symbol() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("app.py"),
+            "class Client:\n    def load(self):\n        fetch(1)\n\ndef main():\n    handler = lambda: fetch(2)\n\nfetch(3)\n",
+        )
+        .unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let output = run_args(&["--within", within, "--query", "call(callee=/^fetch$/)"]);
+        let enclosing: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|event| event["enclosing"].clone())
+            .collect();
+        // The lambda is anonymous, so its call belongs to `main`; the last
+        // call is at module scope
+        assert_eq!(
+            enclosing,
+            [
+                serde_json::json!("load"),
+                serde_json::json!("main"),
+                serde_json::Value::Null
+            ]
+        );
+    }
+
+    #[test]
+    fn edges_link_enclosing_function_to_callees() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("app.py"),
+            "def load():\n    pass\n\ndef main():\n    config = load()\n    serve(config)\n\nmain()\n",
+        )
+        .unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let output = run_args(&["--within", within, "--query", "call()", "--edges"]);
+        let edges: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid json"))
+            .collect();
+        // The top-level `main()` call has no enclosing function
+        assert_eq!(edges.len(), 2, "{output}");
+        assert_eq!(
+            (&edges[0]["from"], &edges[0]["to"]),
+            (&"main".into(), &"load".into())
+        );
+        assert_eq!(
+            (&edges[1]["from"], &edges[1]["to"]),
+            (&"main".into(), &"serve".into())
+        );
+        assert_eq!(edges[0]["line"], 5);
+        assert!(edges[0]["path"].as_str().unwrap().ends_with("app.py"));
+    }
+
+    #[test]
+    fn sarif_format_writes_one_log_of_all_matches() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("a.py"), "fetch()\nfetch()\n").unwrap();
+        std::fs::write(dir.path().join("b.js"), "fetch();\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+        let args = ["--within", within, "--query", "call(callee=/^fetch$/)"];
+
+        let output = run_args(&[&args[..], &["--format", "sarif", "--timings"]].concat());
+        let log: serde_json::Value = serde_json::from_str(&output).expect("one JSON document");
+        assert_eq!(log["version"], "2.1.0");
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results
+            .iter()
+            .all(|r| r["message"]["text"].as_str().unwrap().contains("fetch()")));
+
+        let opt = Opt::parse_from(std::iter::once("ast-find").chain(args).chain([
+            "--format",
+            "sarif",
+            "--count-by",
+            "callee",
+        ]));
+        assert!(run(&opt, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn human_format_prints_headers_and_numbered_excerpts() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let file = dir.path().join("app.py");
+        std::fs::write(&file, "a = 1\nfetch()\nb = 2\n\n\n\nfetch()\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let output = run_args(&[
+            "--within",
+            within,
+            "--query",
+            "call(callee=/^fetch$/)",
+            "--context",
+            "1",
+            "--format",
+            "human",
+        ]);
+        let path = file.to_str().unwrap();
+        assert!(output.contains(&format!("{path}:2:\n1-a = 1\n2:fetch()\n3-b = 2\n")));
+        assert!(output.contains(&format!("--\n{path}:7:\n6-\n7:fetch()\n")));
+        assert!(!output.contains('\x1b'), "no color when not a terminal");
+    }
+
+    #[test]
+    fn timings_summary_lists_every_file() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        for name in ["a.py", "b.py", "c.py"] {
+            std::fs::write(dir.path().join(name), "import os\nos.getcwd()\n").unwrap();
+        }
+        let within = dir.path().to_str().unwrap();
+        let query = "call(callee=/^getcwd$/)";
+
+        let plain = run_args(&["--within", within, "--query", query]);
+        assert!(!plain.contains(r#""type":"summary""#));
+
+        let output = run_args(&["--within", within, "--query", query, "--timings"]);
+        let summary: serde_json::Value =
+            serde_json::from_str(output.lines().last().unwrap()).expect("valid json");
+        assert_eq!(summary["type"], "summary");
+        let timings = summary["stats"]["timings"].as_array().unwrap();
+        assert_eq!(timings.len(), 3);
+        let ms: Vec<f64> = timings.iter().map(|t| t["ms"].as_f64().unwrap()).collect();
+        assert!(ms.iter().all(|&ms| ms > 0.0));
+        assert!(ms.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn count_by_tallies_capture_values() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("calls.py"),
+            "foo(1)\nbar(2)\nfoo(3)\nlog.info(4)\n",
+        )
+        .unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let counts_limited = |field: &str, max_results: &str| {
+            let output = run_args(&[
+                "--within",
+                within,
+                "--query",
+                "call()",
+                "--count-by",
+                field,
+                "--max-results",
+                max_results,
+            ]);
+            output
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("valid json"))
+                .map(|c| {
+                    let value = c["value"].as_str().unwrap().to_string();
+                    (value, c["count"].as_u64().unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        let counts = |field: &str| counts_limited(field, "5000");
+
+        assert_eq!(
+            counts("callee"),
+            [("foo".into(), 2), ("bar".into(), 1), ("info".into(), 1)]
+        );
+        assert_eq!(counts("object"), [("<none>".into(), 3), ("log".into(), 1)]);
+        // The limit trims the tallies, not the matches counted
+        assert_eq!(counts_limited("callee", "1"), [("foo".into(), 2)]);
+
+        let typo = Opt::try_parse_from(["ast-find", "--count-by", "calee"]).unwrap_err();
+        assert!(typo.to_string().contains("unknown capture key"), "{typo}");
+    }
+
+    #[test]
+    fn after_and_before_bound_match_lines() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let mut source = "import os\n\nfetch(1)\n".to_string();
+        source.push_str(&"\n".repeat(26));
+        source.push_str("fetch(30)\n");
+        std::fs::write(dir.path().join("calls.py"), source).unwrap();
+        let within = dir.path().to_str().unwrap();
+        let lines = |extra: &[&str]| {
+            let args = [
+                &["--within", within, "--query", "call(callee=/^fetch$/)"],
+                extra,
+            ]
+            .concat();
+            run_args(&args)
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("valid json"))
+                .map(|event| event["start_line"].as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines(&[]), [3, 30]);
+        assert_eq!(lines(&["--after", "10"]), [30]);
+        assert_eq!(lines(&["--before", "29"]), [3]);
+        assert_eq!(lines(&["--after", "3", "--before", "30"]), [3, 30]);
+    }
+
+    #[test]
+    fn fields_project_match_events() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("calls.py"), "requests.get(url)\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let output = run_args(&[
+            "--within",
+            within,
+            "--query",
+            "call(callee=/^get$/)",
+            "--fields",
+            "path,start_line,callee,capture.object",
+        ]);
+        let event: serde_json::Value =
+            serde_json::from_str(output.lines().next().unwrap()).expect("valid json");
+        let mut keys: Vec<_> = event.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "callee",
+                "capture.object",
+                "path",
+                "start_line",
+                "type",
+                "v"
+            ]
+        );
+        assert_eq!(event["callee"], "get");
+        assert_eq!(event["capture.object"], "requests");
+        assert_eq!(event["start_line"], 1);
+    }
+
+    #[test]
+    fn raw_query_matches_calls_used_as_callees() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("app.py"),
+            "handler = make_handler()\nmake_handler()(request)\n",
+        )
+        .unwrap();
+        let within = dir.path().to_str().unwrap();
+        // A call whose callee is itself a call: a position the DSL cannot name
+        let raw = "(call function: (call function: (identifier) @inner) @match)";
+
+        let output = run_args(&["--within", within, "--lang", "py", "--raw-query", raw]);
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 1, "{output}");
+        assert_eq!(events[0]["start_line"], 2);
+        assert_eq!(events[0]["capture"]["raw"]["inner"], "make_handler");
+        assert_eq!(events[0]["capture"]["callee"], serde_json::Value::Null);
+
+        let run_raw = |lang: &str, raw: &str| {
+            let opt = Opt::parse_from([
+                "ast-find",
+                "--within",
+                within,
+                "--lang",
+                lang,
+                "--raw-query",
+                raw,
+            ]);
+            run(&opt, &mut Vec::new())
+        };
+        let err = run_raw("py", "(call) @call").unwrap_err();
+        assert!(err.to_string().contains("@match"), "{err}");
+        assert!(run_raw("py", "(no_such_node) @match").is_err());
+        assert!(run_raw("py,js", raw).is_err());
+        assert!(Opt::try_parse_from(["ast-find", "--raw-query", raw]).is_err());
+    }
+
+    #[test]
+    fn context_before_and_after_set_an_asymmetric_window() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let source: String = (1..=10)
+            .map(|n| {
+                if n == 5 {
+                    "fetch()\n".to_string()
+                } else {
+                    format!("line{n}\n")
+                }
+            })
+            .collect();
+        std::fs::write(dir.path().join("app.py"), source).unwrap();
+        let within = dir.path().to_str().unwrap();
+        let excerpt = |extra: &[&str]| -> String {
+            let args = [
+                &["--within", within, "--query", "call(callee=/^fetch$/)"][..],
+                extra,
+            ]
+            .concat();
+            let output = run_args(&args);
+            let event: serde_json::Value =
+                serde_json::from_str(output.lines().next().unwrap()).unwrap();
+            event["excerpt"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(
+            excerpt(&["--context-before", "1", "--context-after", "3"]),
+            "line4\nfetch()\nline6\nline7\nline8\n"
+        );
+        // --context fills in the side that is not given
+        assert_eq!(
+            excerpt(&["--context", "2", "--context-after", "0"]),
+            "line3\nline4\nfetch()\n"
+        );
+        assert_eq!(excerpt(&["--context", "1"]), "line4\nfetch()\nline6\n");
+    }
+
+    #[test]
+    fn calls_sharing_a_line_are_all_reported() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("app.py"), "print(len(items))\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let callees = || -> Vec<String> {
+            run_args(&["--within", within, "--query", "call()"])
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .map(|e| {
+                    assert_eq!(e["start_line"], 1);
+                    e["capture"]["callee"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+        // Outer call first, then the one nested in it; same order every run
+        assert_eq!(callees(), ["print", "len"]);
+        assert_eq!(callees(), callees());
+    }
+
+    #[test]
+    fn min_score_drops_less_specific_matches() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("calls.py"),
+            "requests.get(url)\ncache.get(key)\n",
+        )
+        .unwrap();
+        let within = dir.path().to_str().unwrap();
+        let query = "or(call(callee=/^get$/), call(callee=/^get$/, text=/url/))";
+        let scores = |extra: &[&str]| -> Vec<(u64, f64)> {
+            let args = [&["--within", within, "--query", query][..], extra].concat();
+            run_args(&args)
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .map(|e| {
+                    (
+                        e["start_line"].as_u64().unwrap(),
+                        e["score"].as_f64().unwrap(),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(scores(&[]), [(1, 2.0), (2, 1.0)]);
+        assert_eq!(scores(&["--min-score", "1.5"]), [(1, 2.0)]);
+        assert!(scores(&["--min-score", "3"]).is_empty());
+    }
+
+    #[test]
+    fn flatten_capture_hoists_non_null_keys() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(dir.path().join("calls.py"), "requests.get(url)\n").unwrap();
+        let within = dir.path().to_str().unwrap();
+
+        let output = run_args(&[
+            "--within",
+            within,
+            "--query",
+            "call(callee=/^get$/)",
+            "--flatten-capture",
+        ]);
+        let event: serde_json::Value =
+            serde_json::from_str(output.lines().next().unwrap()).expect("valid json");
+        assert!(event.get("capture").is_none());
+        assert_eq!(event["cap_callee"], "get");
+        assert_eq!(event["cap_object"], "requests");
+        assert_eq!(event["cap_raw"]["attr"], "get");
+        assert!(event.get("cap_module").is_none(), "null keys are dropped");
+        assert_eq!(event["path"], format!("{within}/calls.py"));
+        assert_eq!(event["type"], "match");
+
+        assert!(MATCH_FIELDS
+            .iter()
+            .all(|f| !f.starts_with(FLATTENED_PREFIX)));
+        let others: [&[&str]; 3] = [
+            &["--fields", "path"],
+            &["--count-by", "callee"],
+            &["--edges"],
+        ];
+        for other in others {
+            let argv = ["ast-find", "--flatten-capture"].iter().chain(other);
+            assert!(Opt::try_parse_from(argv).is_err(), "{other:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected_at_startup() {
+        let err = Opt::try_parse_from(["ast-find", "--fields", "path,bogus"]).unwrap_err();
+        assert!(err.to_string().contains("unknown field \"bogus\""), "{err}");
+        assert!(parse_fields("type").is_err());
+        assert!(parse_fields("capture.type").is_ok());
+
+        let argv = ["ast-find", "--count-by", "callee", "--fields", "path"];
+        assert!(Opt::try_parse_from(argv).is_err());
+    }
+}
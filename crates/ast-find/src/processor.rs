@@ -1,24 +1,144 @@
 /// File processing logic for ast-find.
 use crate::adapter::{CaptureBundle, LangAdapter};
-use crate::dsl::{Expr, Pred};
-use agent_tools_common::{is_probably_binary, make_chunk_id, slice_with_context, Event, LineIndex};
-use anyhow::Result;
+use crate::dsl::{Expr, Matcher, Pred};
+use agent_tools_common::{
+    is_probably_binary, make_chunk_id, make_content_chunk_id, slice_with_context, Event, LineIndex,
+};
+use anyhow::{Context, Result};
+use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tree_sitter::{Node, Query, QueryCursor, Tree};
 
+/// How the emitted `chunk_id` is derived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ChunkIdMode {
+    /// Hash of `path:start_line-end_line`.
+    #[default]
+    Position,
+    /// Hash of the path and the matched node's source text.
+    Content,
+}
+
+/// What surrounds a match in its `excerpt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ContextMode {
+    /// `context_before` lines above the match and `context_after` below.
+    #[default]
+    Lines,
+    /// The whole enclosing function, method, or class; falls back to
+    /// `Lines` when the match is not inside one.
+    Block,
+}
+
+/// Per-file processing options.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    pub context_before: u32,
+    pub context_after: u32,
+    pub context_mode: ContextMode,
+    pub chunk_id: ChunkIdMode,
+    /// Cut excerpts longer than this many bytes.
+    pub max_excerpt_bytes: Option<usize>,
+    /// Use the matched node's exact source as the excerpt, ignoring
+    /// the context lines and `context_mode`.
+    pub only_matching: bool,
+    /// Skip files that look generated or minified: an average line longer
+    /// than this many bytes, or a generated-code banner near the top.
+    /// Off when `None`.
+    pub max_line_length: Option<usize>,
+    /// Skip files larger than this many bytes, reading no more than that
+    /// much of any file. Unlimited when `None`.
+    pub max_file_size: Option<usize>,
+}
+
+/// Banners that mark a file as generated when found in its first lines.
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// Lines searched for a `GENERATED_MARKERS` banner.
+const BANNER_LINES: usize = 5;
+
+/// Appended to an excerpt cut short by `max_excerpt_bytes`.
+const TRUNCATION_MARKER: &str = "…[truncated]";
+
 #[derive(Debug, Clone)]
 pub struct MatchRecord {
     pub lang: Option<String>,
     pub path: String,
     pub start_line: u32,
     pub end_line: u32,
+    /// Byte range of the matched node, which tells apart matches sharing
+    /// their lines (nested calls on one line). Not part of the emitted event.
+    pub start_byte: usize,
+    pub end_byte: usize,
     pub chunk_id: String,
+    /// How specific the query's hold on this match is; see `pred_weight`.
+    pub score: f32,
     pub excerpt: Option<String>,
+    /// Source line on which `excerpt` begins (it may start above
+    /// `start_line` when context is included). Not part of the emitted event.
+    pub excerpt_line: u32,
     pub capture: serde_json::Value,
+    /// Name of the innermost named function, method, or class around the
+    /// match, if any.
+    pub enclosing: Option<String>,
+}
+
+/// A file's matches keyed by the matched node's byte range, which is unique
+/// per node and sorts in source order.
+type MatchMap = BTreeMap<(usize, usize), MatchRecord>;
+
+/// Compiled queries shared by every file in a run, keyed by adapter name and
+/// the node expression, so each language compiles a query only once.
+///
+/// `tree_sitter::Query` is `Send + Sync` and only read during matching, so
+/// compiled queries are handed out behind an `Arc`.
+#[derive(Default)]
+pub struct QueryCache {
+    compiled: Mutex<HashMap<QueryKey, Arc<Vec<Query>>>>,
+}
+
+/// Adapter name plus the `Debug` rendering of a node expression.
+type QueryKey = (&'static str, String);
+
+impl QueryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_or_compile(&self, adapter: &dyn LangAdapter, expr: &Expr) -> Result<Arc<Vec<Query>>> {
+        let key = (adapter.name(), format!("{:?}", expr));
+        let mut compiled = self.compiled.lock().unwrap();
+        if let Some(queries) = compiled.get(&key) {
+            return Ok(Arc::clone(queries));
+        }
+        let queries = Arc::new(match expr {
+            Expr::Raw(source) => vec![compile_raw(adapter, source)?],
+            _ => adapter.compile(expr)?,
+        });
+        compiled.insert(key, Arc::clone(&queries));
+        Ok(queries)
+    }
 }
 
-type MatchMap = BTreeMap<String, MatchRecord>;
+/// Capture that marks the result node of a raw query.
+pub const RAW_MATCH_CAPTURE: &str = "match";
+
+/// Compile a raw tree-sitter query against `adapter`'s grammar, requiring
+/// the `@match` capture that names each result.
+pub fn compile_raw(adapter: &dyn LangAdapter, source: &str) -> Result<Query> {
+    let query = Query::new(&adapter.language(), source)
+        .with_context(|| format!("invalid raw query for {}", adapter.name()))?;
+    if query.capture_index_for_name(RAW_MATCH_CAPTURE).is_none() {
+        anyhow::bail!(
+            "raw query has no @{} capture to mark the result node",
+            RAW_MATCH_CAPTURE
+        );
+    }
+    Ok(query)
+}
 
 impl From<MatchRecord> for Event {
     fn from(record: MatchRecord) -> Self {
@@ -28,8 +148,9 @@ impl From<MatchRecord> for Event {
             start_line: record.start_line,
             end_line: record.end_line,
             chunk_id: record.chunk_id,
-            score: 1.0,
+            score: record.score,
             excerpt: record.excerpt,
+            enclosing: record.enclosing,
             capture: record.capture,
         }
     }
@@ -41,17 +162,17 @@ struct EvalContext<'a> {
     src: Vec<u8>,
     line_index: LineIndex,
     path: &'a Path,
-    context_lines: u32,
+    opts: &'a ProcessOptions,
     lang_name: &'a str,
     node_cache: HashMap<*const Expr, MatchMap>,
-    query_cache: HashMap<*const Expr, Vec<Query>>,
+    queries: &'a QueryCache,
     universe: Option<MatchMap>,
 }
 
 impl<'a> EvalContext<'a> {
     fn eval_expr(&mut self, expr: &Expr) -> Result<MatchMap> {
         match expr {
-            Expr::Node { .. } => self.eval_node(expr),
+            Expr::Node { .. } | Expr::Raw(_) => self.eval_node(expr),
             Expr::And(children) => self.eval_and(children),
             Expr::Or(children) => self.eval_or(children),
             Expr::Not(child) => self.eval_not(child),
@@ -67,7 +188,14 @@ impl<'a> EvalContext<'a> {
 
         for child in iter {
             let child_set = self.eval_expr(child)?;
-            result.retain(|key, _| child_set.contains_key(key));
+            // A match satisfying every branch earns each branch's score
+            result.retain(|key, record| match child_set.get(key) {
+                Some(other) => {
+                    record.score += other.score;
+                    true
+                }
+                None => false,
+            });
         }
 
         Ok(result)
@@ -78,7 +206,16 @@ impl<'a> EvalContext<'a> {
         for child in children {
             let child_set = self.eval_expr(child)?;
             for (key, record) in child_set {
-                result.entry(key).or_insert(record);
+                match result.entry(key) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(record);
+                    }
+                    // Keep the first branch's capture, but the best score
+                    Entry::Occupied(mut entry) => {
+                        let best = entry.get().score.max(record.score);
+                        entry.get_mut().score = best;
+                    }
+                }
             }
         }
         Ok(result)
@@ -92,10 +229,14 @@ impl<'a> EvalContext<'a> {
             .cloned()
             .unwrap_or_else(MatchMap::new);
 
+        // Matching nothing says little about what did match
         let mut result = universe;
         for key in child_set.keys() {
             result.remove(key);
         }
+        for record in result.values_mut() {
+            record.score = 0.0;
+        }
         Ok(result)
     }
 
@@ -105,25 +246,34 @@ impl<'a> EvalContext<'a> {
             return Ok(cached.clone());
         }
 
-        let queries = self
-            .query_cache
-            .entry(key)
-            .or_insert(self.adapter.compile(expr)?);
+        let queries = self.queries.get_or_compile(self.adapter, expr)?;
 
         let mut map = MatchMap::new();
         for query in queries.iter() {
             let mut cursor = QueryCursor::new();
             let matches = cursor.matches(query, self.tree.root_node(), self.src.as_slice());
 
+            // Raw queries name their result; the adapters' put it first
+            let result = query.capture_index_for_name(RAW_MATCH_CAPTURE);
             for m in matches {
                 let node = m
                     .captures
-                    .first()
+                    .iter()
+                    .find(|c| result.is_none_or(|index| c.index == index))
                     .map(|c| c.node)
                     .unwrap_or(self.tree.root_node());
 
                 let mut bundle = CaptureBundle::new();
                 for capture in m.captures {
+                    // The outer capture is the match node itself; its text
+                    // lives in `__node_text`.
+                    if capture.node.id() == node.id() {
+                        if query.capture_names()[capture.index as usize] == "literal" {
+                            let text = node_text(&self.src, &capture.node);
+                            bundle.insert("value", unquote_literal(&text).to_string());
+                        }
+                        continue;
+                    }
                     let capture_name = query.capture_names()[capture.index as usize];
                     let text = node_text(&self.src, &capture.node);
                     bundle.insert(capture_name.to_string(), text);
@@ -131,50 +281,68 @@ impl<'a> EvalContext<'a> {
 
                 // Store full node text for multi-line predicates.
                 bundle.insert("__node_text", node_text(&self.src, &node));
-
-                if !self.adapter.post_capture_filter(&bundle) {
-                    continue;
+                if let Some(path) = callee_path(&self.src, node) {
+                    bundle.insert("callee_path", path);
                 }
+                bundle.arg_kinds = argument_kinds(node);
+                bundle.chain = chain_calls(&self.src, node);
+                self.adapter.enrich_captures(node, &self.src, &mut bundle);
 
                 let preds = match expr {
-                    Expr::Node { preds, .. } => preds,
+                    Expr::Node { preds, .. } => {
+                        if !self.adapter.post_capture_filter(&bundle) {
+                            continue;
+                        }
+                        preds.as_slice()
+                    }
+                    Expr::Raw(_) => &[],
                     _ => unreachable!(),
                 };
 
-                if !apply_predicates(preds, &bundle) {
+                let Some(score) = apply_predicates(preds, &mut bundle) else {
                     continue;
-                }
+                };
 
                 let start_line = node.start_position().row as u32 + 1;
                 let end_line = node.end_position().row as u32 + 1;
-                let chunk_id = make_chunk_id(self.path, start_line, end_line);
-                let excerpt = slice_with_context(
+                // Matches are keyed by node position so identical snippets,
+                // and distinct nodes on the same lines, stay apart regardless
+                // of the emitted id.
+                let key = (node.start_byte(), node.end_byte());
+                let chunk_id = match self.opts.chunk_id {
+                    ChunkIdMode::Position => make_chunk_id(self.path, start_line, end_line),
+                    ChunkIdMode::Content => make_content_chunk_id(
+                        Some(self.path),
+                        bundle.get("__node_text").unwrap_or_default(),
+                    ),
+                };
+                let (excerpt_line, excerpt) = excerpt(
+                    self.adapter,
                     &self.src,
                     &self.line_index,
-                    start_line,
-                    end_line,
-                    self.context_lines,
+                    self.opts,
+                    node,
+                    (start_line, end_line),
                 );
-
-                let capture_json = serde_json::json!({
-                    "callee": bundle.get("callee_id").or_else(|| bundle.get("prop")),
-                    "object": bundle.get("obj"),
-                    "attr": bundle.get("attr"),
-                    "module": bundle.get("module"),
-                    "name": bundle.get("name"),
-                });
+                let excerpt =
+                    excerpt.map(|text| truncate_excerpt(text, self.opts.max_excerpt_bytes));
 
                 let record = MatchRecord {
                     lang: Some(self.lang_name.to_string()),
                     path: self.path.to_string_lossy().to_string(),
                     start_line,
                     end_line,
-                    chunk_id: chunk_id.clone(),
+                    start_byte: node.start_byte(),
+                    end_byte: node.end_byte(),
+                    chunk_id,
+                    score,
                     excerpt,
-                    capture: capture_json,
+                    excerpt_line,
+                    capture: capture_json(&bundle),
+                    enclosing: enclosing_name(self.adapter, &self.src, node),
                 };
 
-                map.entry(chunk_id).or_insert(record);
+                map.entry(key).or_insert(record);
             }
         }
 
@@ -190,7 +358,7 @@ impl<'a> EvalContext<'a> {
 
     fn collect_node_matches(&mut self, expr: &Expr, map: &mut MatchMap) -> Result<()> {
         match expr {
-            Expr::Node { .. } => {
+            Expr::Node { .. } | Expr::Raw(_) => {
                 let matches = self.eval_node(expr)?;
                 for (key, record) in matches {
                     map.entry(key).or_insert(record);
@@ -209,24 +377,96 @@ impl<'a> EvalContext<'a> {
     }
 }
 
-/// Process a single file with the given adapter and expression.
+/// Process a single file with the given adapter and expression, compiling
+/// its queries from scratch. Batch callers use `process_file_cached`.
 pub fn process_file(
     adapter: &dyn LangAdapter,
     path: &Path,
     expr: &Expr,
-    context_lines: u32,
+    opts: &ProcessOptions,
 ) -> Result<Vec<MatchRecord>> {
-    let src = std::fs::read(path)?;
+    process_file_cached(adapter, path, expr, opts, &QueryCache::new()).map(|file| file.records)
+}
+
+/// Matches found in one file.
+#[derive(Debug, Default)]
+pub struct FileMatches {
+    pub records: Vec<MatchRecord>,
+    /// Wall-clock time spent parsing and evaluating the query (zero for
+    /// skipped binary and generated files).
+    pub elapsed: Duration,
+    /// The tree contains error or missing nodes; matches come from the parts
+    /// that did parse.
+    pub parse_had_errors: bool,
+    /// Why the file was not read at all (too large, or not a regular file).
+    pub skipped: Option<String>,
+}
+
+/// Read `path` in full, or say why it was left unread: it is not a regular
+/// file (a FIFO or device could block or never end), or it holds more than
+/// `max_size` bytes. The read itself stops past `max_size`, so a file that
+/// grows after its size was checked cannot exhaust memory either.
+fn read_source(path: &Path, max_size: Option<usize>) -> Result<Result<Vec<u8>, String>> {
+    use std::io::Read;
+
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Ok(Err("not a regular file".to_string()));
+    }
+    let Some(max) = max_size else {
+        return Ok(Ok(std::fs::read(path)?));
+    };
+    let too_large = |size: u64| format!("{size} bytes exceeds --max-filesize of {max} bytes");
+    if metadata.len() > max as u64 {
+        return Ok(Err(too_large(metadata.len())));
+    }
+
+    let mut src = Vec::with_capacity(metadata.len() as usize);
+    std::fs::File::open(path)?
+        .take(max as u64 + 1)
+        .read_to_end(&mut src)?;
+    if src.len() > max {
+        return Ok(Err(too_large(src.len() as u64)));
+    }
+    Ok(Ok(src))
+}
+
+/// Like `process_file`, reusing queries already compiled into `queries`.
+pub fn process_file_cached(
+    adapter: &dyn LangAdapter,
+    path: &Path,
+    expr: &Expr,
+    opts: &ProcessOptions,
+    queries: &QueryCache,
+) -> Result<FileMatches> {
+    let src = match read_source(path, opts.max_file_size)? {
+        Ok(src) => src,
+        Err(reason) => {
+            return Ok(FileMatches {
+                skipped: Some(reason),
+                ..FileMatches::default()
+            })
+        }
+    };
 
     if is_probably_binary(&src) {
-        return Ok(vec![]);
+        return Ok(FileMatches::default());
+    }
+    if let Some(max) = opts.max_line_length {
+        if is_probably_generated(&src, max) {
+            return Ok(FileMatches::default());
+        }
     }
 
+    let started = Instant::now();
     let mut parser = tree_sitter::Parser::new();
     parser.set_language(&adapter.language())?;
+    // Tree-sitter recovers from syntax errors with ERROR/MISSING nodes, so a
+    // tree is almost always produced and its valid subtrees still match.
     let tree = parser
         .parse(&src, None)
-        .ok_or_else(|| anyhow::anyhow!("Failed to parse file"))?;
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse file: no syntax tree produced"))?;
+    let parse_had_errors = tree.root_node().has_error();
 
     let line_index = LineIndex::new(&src);
     let mut ctx = EvalContext {
@@ -235,10 +475,10 @@ pub fn process_file(
         src,
         line_index,
         path,
-        context_lines,
+        opts,
         lang_name: adapter.name(),
         node_cache: HashMap::new(),
-        query_cache: HashMap::new(),
+        queries,
         universe: None,
     };
 
@@ -246,44 +486,405 @@ pub fn process_file(
     ctx.universe = Some(universe);
 
     let matches = ctx.eval_expr(expr)?;
-    Ok(matches.into_values().collect())
+    Ok(FileMatches {
+        records: matches.into_values().collect(),
+        elapsed: started.elapsed(),
+        parse_had_errors,
+        skipped: None,
+    })
 }
 
-/// Apply DSL predicates to a capture bundle.
-fn apply_predicates(preds: &[Pred], bundle: &CaptureBundle) -> bool {
-    for pred in preds {
-        let matched = match pred {
-            Pred::Callee(re) => {
-                bundle
-                    .get("callee_id")
-                    .map(|t| re.is_match(t))
-                    .unwrap_or(false)
-                    || bundle.get("prop").map(|t| re.is_match(t)).unwrap_or(false)
-            }
-            Pred::Name(re) => bundle.get("name").map(|t| re.is_match(t)).unwrap_or(false),
-            Pred::Module(re) => bundle
-                .get("module")
-                .map(|t| re.is_match(t))
-                .unwrap_or(false),
-            Pred::Prop(re) => bundle.get("prop").map(|t| re.is_match(t)).unwrap_or(false),
-            Pred::Arg(_) => {
-                // TODO: Implement argument matching
-                true
+/// Whether `src` looks machine-written: minified (average line longer than
+/// `max_line_length` bytes) or carrying a generated-code banner at the top.
+fn is_probably_generated(src: &[u8], max_line_length: usize) -> bool {
+    let lines = src.split(|&b| b == b'\n');
+    let (count, bytes) = lines
+        .clone()
+        .filter(|line| !line.is_empty())
+        .fold((0, 0), |(count, bytes), line| {
+            (count + 1, bytes + line.len())
+        });
+    if count > 0 && bytes / count > max_line_length {
+        return true;
+    }
+    lines.take(BANNER_LINES).any(|line| {
+        let line = String::from_utf8_lossy(line);
+        GENERATED_MARKERS.iter().any(|marker| line.contains(marker))
+    })
+}
+
+/// Source shown for a match, with the line it starts on: surrounding lines,
+/// under `ContextMode::Block` the nearest enclosing block the adapter
+/// recognizes, or with `only_matching` just the node itself.
+fn excerpt(
+    adapter: &dyn LangAdapter,
+    src: &[u8],
+    line_index: &LineIndex,
+    opts: &ProcessOptions,
+    node: Node,
+    (start_line, end_line): (u32, u32),
+) -> (u32, Option<String>) {
+    if opts.only_matching {
+        return (start_line, Some(node_text(src, &node)));
+    }
+    if opts.context_mode == ContextMode::Block {
+        let kinds = adapter.enclosing_block_kinds();
+        let block =
+            std::iter::successors(Some(node), |n| n.parent()).find(|n| kinds.contains(&n.kind()));
+        if let Some(block) = block {
+            let start = block.start_position().row as u32 + 1;
+            let end = block.end_position().row as u32 + 1;
+            return (start, slice_with_context(src, line_index, start, end, 0, 0));
+        }
+    }
+    let first = start_line.saturating_sub(opts.context_before).max(1);
+    let text = slice_with_context(
+        src,
+        line_index,
+        start_line,
+        end_line,
+        opts.context_before,
+        opts.context_after,
+    );
+    (first, text)
+}
+
+/// Name of the nearest block above `node` (never `node` itself) that the
+/// adapter recognizes and that has a `name` field, or that initializes a JS/TS
+/// variable (`const handler = () => …`). Other anonymous functions are skipped
+/// in favour of the named block around them.
+fn enclosing_name(adapter: &dyn LangAdapter, src: &[u8], node: Node) -> Option<String> {
+    let kinds = adapter.enclosing_block_kinds();
+    std::iter::successors(node.parent(), |n| n.parent())
+        .filter(|n| kinds.contains(&n.kind()))
+        .find_map(|n| {
+            n.child_by_field_name("name").or_else(|| {
+                n.parent()
+                    .filter(|p| p.kind() == "variable_declarator")
+                    .and_then(|p| p.child_by_field_name("name"))
+            })
+        })
+        .map(|name| node_text(src, &name))
+}
+
+/// Cut `text` to at most `max_bytes` bytes, backing off to a character
+/// boundary, and mark the cut.
+fn truncate_excerpt(mut text: String, max_bytes: Option<usize>) -> String {
+    let Some(max_bytes) = max_bytes.filter(|&max| text.len() > max) else {
+        return text;
+    };
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(TRUNCATION_MARKER);
+    text
+}
+
+/// Strip the quotes, prefixes and raw-string delimiters from a literal's
+/// source text, e.g. `r#"x"#`, `b'x'`, `@"x"`, `"""x"""`. Numbers and
+/// anything else without surrounding quotes come back unchanged.
+fn unquote_literal(text: &str) -> &str {
+    let body = text.trim_start_matches(|c: char| c.is_ascii_alphabetic() || c == '@' || c == '$');
+    let hashes = body.len() - body.trim_start_matches('#').len();
+    let body = &body[hashes..];
+    let Some(quote) = body
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))
+    else {
+        return text;
+    };
+    let quotes = body.len() - body.trim_start_matches(quote).len();
+    // Three or more quotes open a triple-quoted or raw string; two are an
+    // empty string.
+    let quotes = if quotes >= 3 { quotes } else { 1 };
+    let close = format!("{}{}", quote.to_string().repeat(quotes), "#".repeat(hashes));
+    match body
+        .get(quotes..)
+        .and_then(|rest| rest.strip_suffix(close.as_str()))
+    {
+        Some(inner) => inner,
+        None => text,
+    }
+}
+
+/// Apply DSL predicates to a capture bundle, returning the match's score when
+/// all of them hold.
+fn apply_predicates(preds: &[Pred], bundle: &mut CaptureBundle) -> Option<f32> {
+    let mut groups = Vec::new();
+    if !preds
+        .iter()
+        .all(|pred| eval_pred(pred, bundle, &mut groups))
+    {
+        return None;
+    }
+    bundle.groups.extend(groups);
+    Some(preds.iter().map(pred_weight).sum())
+}
+
+/// What a satisfied predicate adds to a match's score. Exact comparisons
+/// (`"literal"`, `argkind=`) pin a match down more than a glob, and a glob
+/// more than a regex; a negation only rules other things out. A node with
+/// no predicates scores 0, so by default every match is kept.
+fn pred_weight(pred: &Pred) -> f32 {
+    let matcher = match pred {
+        Pred::Callee(m)
+        | Pred::Name(m)
+        | Pred::Module(m)
+        | Pred::Prop(m)
+        | Pred::Text(m)
+        | Pred::Type(m)
+        | Pred::Trait(m)
+        | Pred::Method(m)
+        | Pred::Embed(m)
+        | Pred::Generics(m)
+        | Pred::Value(m)
+        | Pred::Alias(m)
+        | Pred::Member(m)
+        | Pred::Chain(m) => m,
+        // Not evaluated, so it narrows nothing
+        Pred::Arg(_) => return 0.0,
+        Pred::ArgKind { .. } => return 2.0,
+        Pred::Not(_) => return 0.5,
+    };
+    match matcher {
+        Matcher::Literal(_) => 2.0,
+        Matcher::Glob(_) => 1.5,
+        Matcher::Regex(_) => 1.0,
+    }
+}
+
+/// Evaluate one predicate, collecting the named regex groups of whatever it
+/// matched into `groups`.
+fn eval_pred(pred: &Pred, bundle: &CaptureBundle, groups: &mut Vec<(String, String)>) -> bool {
+    let mut check = |m: &Matcher, text: Option<&str>| match text {
+        Some(text) if m.is_match(text) => {
+            groups.extend(m.named_groups(text));
+            true
+        }
+        _ => false,
+    };
+
+    match pred {
+        Pred::Callee(m) => {
+            check(m, bundle.get("callee_id"))
+                || check(m, member_name(bundle))
+                || check(m, bundle.get("callee_path"))
+        }
+        Pred::Name(m) => check(m, bundle.get("name")),
+        // Rust brace groups also match by each path they expand to
+        Pred::Module(m) => {
+            check(m, bundle.get("module"))
+                || bundle
+                    .get_list("use_path")
+                    .iter()
+                    .any(|t| check(m, Some(t)))
+        }
+        Pred::Prop(m) => check(m, member_name(bundle)),
+        Pred::Arg(_) => {
+            // TODO: Implement argument matching
+            true
+        }
+        Pred::ArgKind { index, kind } => index
+            .checked_sub(1)
+            .and_then(|i| bundle.arg_kinds.get(i))
+            .is_some_and(|actual| actual == kind),
+        Pred::Text(m) => check(m, bundle.get("__node_text")),
+        Pred::Type(m) => check(m, bundle.get("type")),
+        Pred::Trait(m) => check(m, bundle.get("trait")),
+        Pred::Method(m) => bundle.get_list("method").iter().any(|t| check(m, Some(t))),
+        Pred::Embed(m) => bundle.get_list("embed").iter().any(|t| check(m, Some(t))),
+        Pred::Generics(m) => check(m, bundle.get("generics")),
+        Pred::Value(m) => check(m, bundle.get("value")),
+        Pred::Alias(m) => bundle.get_list("alias").iter().any(|t| check(m, Some(t))),
+        Pred::Member(m) => bundle.get_list("member").iter().any(|t| check(m, Some(t))),
+        Pred::Chain(m) => bundle.chain.iter().any(|t| check(m, Some(t))),
+        // A negated predicate has no match to take groups from
+        Pred::Not(inner) => !eval_pred(inner, bundle, &mut Vec::new()),
+    }
+}
+
+/// Keys of the `capture` object built by `capture_json`.
+pub const CAPTURE_FIELDS: &[&str] = &[
+    "callee",
+    "callee_path",
+    "object",
+    "member",
+    "module",
+    "name",
+    "type",
+    "trait",
+    "methods",
+    "embeds",
+    "generics",
+    "aliases",
+    "members",
+    "args",
+    "target",
+    "value",
+    "groups",
+    "raw",
+];
+
+/// Build the normalized `capture` object emitted on each match.
+///
+/// Top-level keys mean the same thing in every language; the adapter's
+/// original capture names are kept under `raw`.
+fn capture_json(bundle: &CaptureBundle) -> serde_json::Value {
+    let member = member_name(bundle);
+    let mut raw = serde_json::Map::new();
+    for (key, value) in &bundle.texts {
+        if !key.starts_with("__") {
+            raw.insert(key.clone(), serde_json::json!(value));
+        }
+    }
+    for (key, values) in &bundle.lists {
+        raw.insert(key.clone(), serde_json::json!(values));
+    }
+
+    serde_json::json!({
+        "callee": bundle.get("callee_id").or(member),
+        "callee_path": bundle.get("callee_path"),
+        "object": bundle.get("obj"),
+        "member": member,
+        "module": bundle.get("module"),
+        "name": bundle.get("name"),
+        "type": bundle.get("type"),
+        "trait": bundle.get("trait"),
+        "methods": list_capture(bundle, "method"),
+        "embeds": list_capture(bundle, "embed"),
+        "generics": bundle.get("generics"),
+        "aliases": list_capture(bundle, "alias"),
+        "members": list_capture(bundle, "member"),
+        "args": bundle.get("args"),
+        "target": bundle.get("target"),
+        "value": bundle.get("value"),
+        "groups": (!bundle.groups.is_empty()).then_some(&bundle.groups),
+        "raw": raw,
+    })
+}
+
+/// Full source path of a call's callee, e.g. `a.b.c.get` or `std::fs::read`,
+/// with whitespace from multi-line chains removed. Turbofish arguments on a
+/// Rust generic call are dropped. `None` for nodes without a `function` field.
+fn callee_path(src: &[u8], call: Node) -> Option<String> {
+    let mut function = call.child_by_field_name("function")?;
+    if function.kind() == "generic_function" {
+        function = function.child_by_field_name("function")?;
+    }
+    let mut path = node_text(src, &function);
+    path.retain(|c| !c.is_whitespace());
+    Some(path)
+}
+
+/// Node kinds of a call's arguments, e.g. `["string", "identifier"]`. C#
+/// wraps each argument in an `argument` node, which is looked through; a lone
+/// Python generator argument (`f(x for x in y)`) is its own argument list.
+/// Empty for nodes without an `arguments` field.
+fn argument_kinds(call: Node) -> Vec<String> {
+    let Some(args) = call.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+    if args.kind() == "generator_expression" {
+        return vec![args.kind().to_string()];
+    }
+    let mut cursor = args.walk();
+    let kinds = args
+        .named_children(&mut cursor)
+        .filter(|arg| !arg.is_extra())
+        .map(|arg| match arg.kind() {
+            "argument" => arg
+                .named_child(arg.named_child_count().saturating_sub(1))
+                .map_or("argument", |expr| expr.kind()),
+            kind => kind,
+        })
+        .map(str::to_string)
+        .collect();
+    kinds
+}
+
+/// Member-access node kinds whose `object` a fluent chain continues through,
+/// with the field naming the called member: JS/TS and Python.
+const CHAIN_LINKS: &[(&str, &str)] = &[
+    ("member_expression", "property"),
+    ("attribute", "attribute"),
+];
+
+/// Every name called along the fluent chain `call` belongs to, from the
+/// first link to the last: `query().where(a).limit(1).build()` gives
+/// `["query", "where", "limit", "build"]` for any of its four calls. Empty
+/// unless the chain has a member call in a grammar listed in `CHAIN_LINKS`.
+fn chain_calls(src: &[u8], call: Node) -> Vec<String> {
+    let link_field = |node: Node| {
+        CHAIN_LINKS
+            .iter()
+            .find(|(kind, _)| *kind == node.kind())
+            .map(|&(_, field)| field)
+    };
+    let is_function_of = |inner: Node, call: Node| {
+        call.child_by_field_name("function")
+            .is_some_and(|function| function.id() == inner.id())
+    };
+
+    // Climb to the last call of the chain: while this call is the object of
+    // a member access that is itself called
+    let mut top = call;
+    while let Some(member) = top.parent() {
+        let continues = link_field(member).is_some()
+            && member
+                .child_by_field_name("object")
+                .is_some_and(|object| object.id() == top.id());
+        match member.parent() {
+            Some(outer) if continues && is_function_of(member, outer) => top = outer,
+            _ => break,
+        }
+    }
+
+    // Walk back down through each callee's object
+    let mut names = Vec::new();
+    let mut linked = false;
+    let mut current = top;
+    while let Some(function) = current.child_by_field_name("function") {
+        let Some(field) = link_field(function) else {
+            // The chain starts with a plain call: `query()`
+            if function.kind() == "identifier" {
+                names.push(node_text(src, &function));
             }
-            Pred::Text(re) => bundle
-                .get("__node_text")
-                .map(|t| re.is_match(t))
-                .unwrap_or(false),
+            break;
         };
-
-        if !matched {
-            return false;
+        linked = true;
+        if let Some(name) = function.child_by_field_name(field) {
+            names.push(node_text(src, &name));
         }
+        match function.child_by_field_name("object") {
+            Some(object) if object.child_by_field_name("function").is_some() => current = object,
+            _ => break,
+        }
+    }
+    if !linked {
+        return Vec::new();
+    }
+    names.reverse();
+    names
+}
+
+/// Property name of a member call. Python's adapter captures it as `attr`,
+/// the others as `prop`.
+fn member_name(bundle: &CaptureBundle) -> Option<&str> {
+    bundle.get("prop").or_else(|| bundle.get("attr"))
+}
+
+/// Render a multi-valued capture as a JSON array, or `null` when absent.
+fn list_capture(bundle: &CaptureBundle, key: &str) -> serde_json::Value {
+    match bundle.get_list(key) {
+        [] => serde_json::Value::Null,
+        items => serde_json::json!(items),
     }
-    true
 }
 
-fn node_text(src: &[u8], node: &Node) -> String {
+pub(crate) fn node_text(src: &[u8], node: &Node) -> String {
     let start = node.start_byte();
     let end = node.end_byte();
     String::from_utf8_lossy(&src[start..end]).to_string()
@@ -293,10 +894,77 @@ fn node_text(src: &[u8], node: &Node) -> String {
 mod tests {
     use super::*;
     use crate::dsl::parse_query;
-    use crate::languages::JavaScriptAdapter;
+    use crate::languages::{JavaScriptAdapter, PythonAdapter, RustAdapter};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn score_grows_with_predicate_specificity() {
+        let mut temp = NamedTempFile::new().expect("create temp file");
+        writeln!(temp, "requests.get(url)").expect("write temp file");
+        let score = |query: &str| {
+            let expr = parse_query(query).expect("parse query");
+            let matches = process_file(
+                &PythonAdapter,
+                temp.path(),
+                &expr,
+                &ProcessOptions::default(),
+            )
+            .expect("process file");
+            assert_eq!(matches.len(), 1, "{query}");
+            matches[0].score
+        };
+
+        let one = score("call(callee=/^get$/)");
+        let two = score("call(callee=/^get$/, text=/url/)");
+        assert!(two > one, "{two} > {one}");
+        assert!(score("call(callee=\"get\")") > one);
+        assert_eq!(score("call()"), 0.0);
+        assert_eq!(score("and(call(callee=/^get$/), call(text=/url/))"), two);
+        assert_eq!(score("or(call(callee=/^get$/), call(callee=\"get\"))"), 2.0);
+    }
+
+    #[test]
+    fn chain_predicate_matches_any_link_of_a_fluent_chain() {
+        let lines = |adapter: &dyn LangAdapter, source: &str, query: &str| {
+            let mut temp = NamedTempFile::new().expect("create temp file");
+            write!(temp, "{source}").expect("write temp file");
+            let expr = parse_query(query).expect("parse query");
+            let mut matches = process_file(adapter, temp.path(), &expr, &ProcessOptions::default())
+                .expect("process file");
+            matches.sort_by_key(|m| m.start_byte);
+            matches.iter().map(|m| m.start_line).collect::<Vec<_>>()
+        };
+
+        let js = "query().where(a).raw(sql).build();\ndb.build();\nraw();\n";
+        assert_eq!(
+            lines(&JavaScriptAdapter, js, "call(chain=/^raw$/)"),
+            [1, 1, 1, 1]
+        );
+        assert_eq!(
+            lines(
+                &JavaScriptAdapter,
+                js,
+                "call(chain=/^query$/, prop=/^build$/)"
+            ),
+            [1]
+        );
+        assert_eq!(
+            lines(&JavaScriptAdapter, js, "call(chain=/^db$/)"),
+            Vec::<u32>::new()
+        );
+
+        let py = "session.query(User).filter(x).raw(sql).all()\nsession.query(User).all()\n";
+        assert_eq!(
+            lines(&PythonAdapter, py, "call(chain=/^raw$/)"),
+            [1, 1, 1, 1]
+        );
+        assert_eq!(
+            lines(&PythonAdapter, py, "call(chain=/^filter$/, callee=/^all$/)"),
+            [1]
+        );
+    }
+
     #[test]
     fn multi_line_text_predicate_matches() {
         let mut temp = NamedTempFile::new().expect("create temp file");
@@ -319,14 +987,356 @@ mod tests {
 
         let adapter = JavaScriptAdapter;
         let sanity_expr = parse_query("call(prop=/^get$/)").expect("parse sanity query");
-        let sanity_matches = process_file(&adapter, temp.path(), &sanity_expr, 0)
-            .expect("process sanity file");
+        let sanity_matches = process_file(
+            &adapter,
+            temp.path(),
+            &sanity_expr,
+            &ProcessOptions::default(),
+        )
+        .expect("process sanity file");
         assert_eq!(sanity_matches.len(), 1);
 
-        let expr = parse_query(r"call(text=/axios\.get\(.*Authorization/)")
-            .expect("parse query");
-        let matches = process_file(&adapter, temp.path(), &expr, 0)
+        let expr = parse_query(r"call(text=/axios\.get\(.*Authorization/)").expect("parse query");
+        let matches = process_file(&adapter, temp.path(), &expr, &ProcessOptions::default())
             .expect("process file");
         assert_eq!(matches.len(), 1);
     }
+
+    fn member_capture(adapter: &dyn LangAdapter, ext: &str, source: &str) -> serde_json::Value {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join(format!("member.{ext}"));
+        std::fs::write(&path, source).expect("write file");
+        let expr = parse_query("call(prop=/^get$/)").expect("parse query");
+        let matches =
+            process_file(adapter, &path, &expr, &ProcessOptions::default()).expect("process file");
+        assert_eq!(matches.len(), 1, "{ext}");
+        matches[0].capture.clone()
+    }
+
+    #[test]
+    fn member_calls_share_normalized_capture_shape() {
+        let cases: [(&dyn LangAdapter, &str, &str, &str); 3] = [
+            (&PythonAdapter, "py", "client.get(url)\n", "attr"),
+            (&JavaScriptAdapter, "js", "client.get(url);\n", "prop"),
+            (&RustAdapter, "rs", "fn f() { client.get(url); }\n", "prop"),
+        ];
+
+        for (adapter, ext, source, raw_key) in cases {
+            let capture = member_capture(adapter, ext, source);
+            assert_eq!(capture["member"], "get", "{ext}");
+            assert_eq!(capture["callee"], "get", "{ext}");
+            assert_eq!(capture["object"], "client", "{ext}");
+            assert_eq!(capture["raw"][raw_key], "get", "{ext}");
+            assert_eq!(capture["raw"]["obj"], "client", "{ext}");
+            assert!(capture.get("attr").is_none(), "{ext}");
+            assert!(capture["raw"].get("call").is_none(), "{ext}");
+        }
+    }
+
+    fn chunk_ids_before_and_after_shift(mode: ChunkIdMode) -> (String, String) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("shift.js");
+        let adapter = JavaScriptAdapter;
+        let expr = parse_query("call(callee=/^fetchData$/)").expect("parse query");
+        let opts = ProcessOptions {
+            chunk_id: mode,
+            ..ProcessOptions::default()
+        };
+
+        std::fs::write(&path, "fetchData(1);\n").expect("write file");
+        let before = process_file(&adapter, &path, &expr, &opts).expect("process file");
+        std::fs::write(&path, "// moved down\n\nfetchData(1);\n").expect("rewrite file");
+        let after = process_file(&adapter, &path, &expr, &opts).expect("process file");
+
+        assert_eq!(before.len(), 1);
+        assert_eq!(after.len(), 1);
+        assert_ne!(before[0].start_line, after[0].start_line);
+        (before[0].chunk_id.clone(), after[0].chunk_id.clone())
+    }
+
+    fn excerpt_for(mode: ContextMode) -> String {
+        let mut temp = NamedTempFile::new().expect("create temp file");
+        write!(
+            temp,
+            "import os\n\n\ndef outer(x):\n    y = x + 1\n    helper(y)\n    return y\n\n\nprint(outer(1))\n"
+        )
+        .expect("write temp file");
+        let expr = parse_query("call(callee=/^helper$/)").expect("parse query");
+        let opts = ProcessOptions {
+            context_before: 1,
+            context_after: 1,
+            context_mode: mode,
+            ..ProcessOptions::default()
+        };
+        let matches = process_file(&PythonAdapter, temp.path(), &expr, &opts).expect("process");
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].start_line, matches[0].end_line), (6, 6));
+        matches[0].excerpt.clone().expect("excerpt")
+    }
+
+    #[test]
+    fn line_context_surrounds_match() {
+        assert_eq!(
+            excerpt_for(ContextMode::Lines),
+            "    y = x + 1\n    helper(y)\n    return y\n"
+        );
+    }
+
+    #[test]
+    fn block_context_covers_enclosing_function() {
+        assert_eq!(
+            excerpt_for(ContextMode::Block),
+            "def outer(x):\n    y = x + 1\n    helper(y)\n    return y\n"
+        );
+    }
+
+    #[test]
+    fn generated_and_minified_files_are_skipped() {
+        let expr = parse_query("call(callee=/^init$/)").expect("parse query");
+        let opts = ProcessOptions {
+            max_line_length: Some(500),
+            ..ProcessOptions::default()
+        };
+        let run = |source: &str, opts: &ProcessOptions| {
+            let mut temp = NamedTempFile::new().expect("create temp file");
+            temp.write_all(source.as_bytes()).expect("write temp file");
+            process_file(&JavaScriptAdapter, temp.path(), &expr, opts).expect("process")
+        };
+
+        let normal = "import { init } from './app';\n\ninit({ debug: true });\n";
+        assert_eq!(run(normal, &opts).len(), 1);
+
+        let minified = format!(
+            "!function(){{{}init(1)}}();\n",
+            "var a=function(b){return b*2};".repeat(40)
+        );
+        assert!(run(&minified, &opts).is_empty());
+        assert_eq!(run(&minified, &ProcessOptions::default()).len(), 1);
+
+        let banner = format!("// Code generated by protoc. DO NOT EDIT.\n{normal}");
+        assert!(run(&banner, &opts).is_empty());
+        let late_mention = format!("{normal}\n\n\n\n// DO NOT EDIT below this line\n");
+        assert_eq!(run(&late_mention, &opts).len(), 1);
+    }
+
+    #[test]
+    fn oversized_files_are_skipped_unread() {
+        let expr = parse_query("call(callee=/^init$/)").expect("parse query");
+        let mut temp = NamedTempFile::new().expect("create temp file");
+        write!(temp, "init();\n{}", "// padding\n".repeat(100)).expect("write temp file");
+        let opts = |max_file_size| ProcessOptions {
+            max_file_size,
+            ..ProcessOptions::default()
+        };
+        let run = |opts: &ProcessOptions| {
+            process_file_cached(
+                &JavaScriptAdapter,
+                temp.path(),
+                &expr,
+                opts,
+                &QueryCache::new(),
+            )
+            .expect("process")
+        };
+
+        let skipped = run(&opts(Some(1024)));
+        assert!(skipped.records.is_empty());
+        let reason = skipped.skipped.expect("skip reason");
+        assert!(
+            reason.contains("1108 bytes exceeds --max-filesize"),
+            "{reason}"
+        );
+
+        let searched = run(&opts(Some(2048)));
+        assert_eq!(searched.records.len(), 1);
+        assert_eq!(searched.skipped, None);
+        assert_eq!(run(&opts(None)).records.len(), 1);
+
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let not_a_file = process_file_cached(
+            &JavaScriptAdapter,
+            dir.path(),
+            &expr,
+            &opts(Some(1024)),
+            &QueryCache::new(),
+        )
+        .expect("process");
+        assert_eq!(not_a_file.skipped.as_deref(), Some("not a regular file"));
+    }
+
+    #[test]
+    fn only_matching_excerpt_is_exact_node_source() {
+        let mut temp = NamedTempFile::new().expect("create temp file");
+        write!(
+            temp,
+            "x = 1\nresult = helper(\n    x,\n    key=\"v\",\n)  # trailing\n"
+        )
+        .expect("write temp file");
+        let expr = parse_query("call(callee=/^helper$/)").expect("parse query");
+        let opts = ProcessOptions {
+            context_before: 3,
+            context_after: 3,
+            only_matching: true,
+            ..ProcessOptions::default()
+        };
+        let matches = process_file(&PythonAdapter, temp.path(), &expr, &opts).expect("process");
+        assert_eq!((matches[0].start_line, matches[0].end_line), (2, 5));
+        assert_eq!(
+            matches[0].excerpt.as_deref(),
+            Some("helper(\n    x,\n    key=\"v\",\n)")
+        );
+
+        let opts = ProcessOptions {
+            max_excerpt_bytes: Some(6),
+            ..opts
+        };
+        let matches = process_file(&PythonAdapter, temp.path(), &expr, &opts).expect("process");
+        assert_eq!(matches[0].excerpt.as_deref(), Some("helper…[truncated]"));
+    }
+
+    #[test]
+    fn long_excerpts_are_truncated_on_char_boundary() {
+        let mut temp = NamedTempFile::new().expect("create temp file");
+        write!(
+            temp,
+            "def greet():\n    return \"héllo wörld, ünïcode everywhere\"\n"
+        )
+        .expect("write temp file");
+        let expr = parse_query("def(name=/^greet$/)").expect("parse query");
+
+        // Byte 27 falls inside the two-byte "é"
+        let opts = ProcessOptions {
+            max_excerpt_bytes: Some(27),
+            ..ProcessOptions::default()
+        };
+        let matches = process_file(&PythonAdapter, temp.path(), &expr, &opts).expect("process");
+        let excerpt = matches[0].excerpt.clone().expect("excerpt");
+        assert_eq!(excerpt, "def greet():\n    return \"h…[truncated]");
+
+        let opts = ProcessOptions {
+            max_excerpt_bytes: Some(1000),
+            ..ProcessOptions::default()
+        };
+        let matches = process_file(&PythonAdapter, temp.path(), &expr, &opts).expect("process");
+        assert!(!matches[0]
+            .excerpt
+            .as_ref()
+            .unwrap()
+            .ends_with(TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn syntax_errors_do_not_hide_valid_matches() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("partial.py");
+        std::fs::write(
+            &path,
+            "def broken(:\n    fetch(1)\n\n\ndef fine():\n    fetch(2)\n",
+        )
+        .expect("write file");
+        let expr = parse_query("def(name=/^fine$/)").expect("parse query");
+
+        let file = process_file_cached(
+            &PythonAdapter,
+            &path,
+            &expr,
+            &ProcessOptions::default(),
+            &QueryCache::new(),
+        )
+        .expect("partial tree is still searched");
+        assert!(file.parse_had_errors);
+        assert_eq!(file.records.len(), 1);
+        assert_eq!(file.records[0].start_line, 5);
+    }
+
+    #[test]
+    fn named_regex_groups_appear_in_capture() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("version.py");
+        std::fs::write(&path, "VERSION = \"v1.2\"\nNAME = \"app\"\n").expect("write file");
+        let expr = parse_query(r"literal(value=/^v(?P<ver>\d+\.\d+)$/)").expect("parse query");
+
+        let matches = process_file(&PythonAdapter, &path, &expr, &ProcessOptions::default())
+            .expect("process");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].capture["groups"],
+            serde_json::json!({ "ver": "1.2" })
+        );
+
+        let expr = parse_query("literal(value=/app/)").expect("parse query");
+        let matches = process_file(&PythonAdapter, &path, &expr, &ProcessOptions::default())
+            .expect("process");
+        assert!(matches[0].capture["groups"].is_null());
+    }
+
+    /// Python adapter that counts how often it compiles a query.
+    #[derive(Default)]
+    struct CountingAdapter {
+        compiles: std::sync::atomic::AtomicUsize,
+    }
+
+    impl LangAdapter for CountingAdapter {
+        fn name(&self) -> &'static str {
+            PythonAdapter.name()
+        }
+
+        fn language(&self) -> tree_sitter::Language {
+            PythonAdapter.language()
+        }
+
+        fn compile(&self, expr: &Expr) -> Result<Vec<Query>> {
+            self.compiles
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            PythonAdapter.compile(expr)
+        }
+    }
+
+    #[test]
+    fn queries_compile_once_per_language() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let expr = parse_query("or(call(callee=/^run$/), def(name=/^run$/))").expect("parse");
+        let adapter = CountingAdapter::default();
+        let cache = QueryCache::new();
+
+        for i in 0..3 {
+            let path = dir.path().join(format!("f{i}.py"));
+            std::fs::write(&path, "def run():\n    pass\n\nrun()\n").expect("write file");
+            let matches =
+                process_file_cached(&adapter, &path, &expr, &ProcessOptions::default(), &cache)
+                    .expect("process file");
+            assert_eq!(matches.records.len(), 2);
+        }
+
+        // One compile per node expression, not per file
+        assert_eq!(
+            adapter.compiles.load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+    }
+
+    #[test]
+    fn content_chunk_ids_are_stable_under_line_shifts() {
+        let (before, after) = chunk_ids_before_and_after_shift(ChunkIdMode::Content);
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn position_chunk_ids_change_under_line_shifts() {
+        let (before, after) = chunk_ids_before_and_after_shift(ChunkIdMode::Position);
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn unquote_literal_handles_prefixes_and_raw_strings() {
+        assert_eq!(unquote_literal(r#""a""#), "a");
+        assert_eq!(unquote_literal("'a'"), "a");
+        assert_eq!(unquote_literal("`a`"), "a");
+        assert_eq!(unquote_literal(r###"r#"a"b"#"###), "a\"b");
+        assert_eq!(unquote_literal(r#"b"a""#), "a");
+        assert_eq!(unquote_literal(r#"@"a""#), "a");
+        assert_eq!(unquote_literal(r#""""a""""#), "a");
+        assert_eq!(unquote_literal(r#""""#), "");
+        assert_eq!(unquote_literal("42"), "42");
+    }
 }
@@ -0,0 +1,207 @@
+/// SARIF 2.1.0 rendering of a search, for code-scanning UIs such as GitHub's.
+///
+/// The whole run becomes one `run` with a single rule standing for the query;
+/// every match is a `result` of that rule, and error events become tool
+/// execution notifications.
+use agent_tools_common::Event;
+use serde_json::{json, Value};
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Stable rule id for `query`: its node kind plus a short hash of the full
+/// text, e.g. `call-1a2b3c4d`, so reruns of the same query line up in CI.
+pub fn rule_id(query: &str) -> String {
+    let kind: String = query
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    let hash = blake3::hash(query.as_bytes()).to_hex();
+    let kind = if kind.is_empty() { "query" } else { &kind };
+    format!("{}-{}", kind, &hash[..8])
+}
+
+/// Build the SARIF log for `query` from the match and error events of a run,
+/// in the order given.
+pub fn document<'a>(query: &str, events: impl IntoIterator<Item = &'a Event>) -> Value {
+    let rule_id = rule_id(query);
+    let mut results = Vec::new();
+    let mut notifications = Vec::new();
+
+    for event in events {
+        match event {
+            Event::Match {
+                path,
+                start_line,
+                end_line,
+                chunk_id,
+                excerpt,
+                ..
+            } => {
+                let snippet = excerpt.as_deref().unwrap_or_default();
+                let message = match snippet.trim() {
+                    "" => format!("Matches {}", query),
+                    text => text.to_string(),
+                };
+                let mut region = json!({
+                    "startLine": start_line,
+                    "endLine": end_line,
+                });
+                if !snippet.is_empty() {
+                    region["snippet"] = json!({ "text": snippet });
+                }
+                results.push(json!({
+                    "ruleId": rule_id,
+                    "ruleIndex": 0,
+                    "level": "note",
+                    "message": { "text": message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": artifact_uri(path) },
+                            "region": region,
+                        }
+                    }],
+                    "partialFingerprints": { "chunkId/v1": chunk_id },
+                }));
+            }
+            Event::Error {
+                message,
+                path_or_url,
+                ..
+            } => {
+                let mut notification = json!({
+                    "level": "error",
+                    "message": { "text": message },
+                });
+                if let Some(path) = path_or_url {
+                    notification["locations"] = json!([{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": artifact_uri(path) }
+                        }
+                    }]);
+                }
+                notifications.push(notification);
+            }
+            _ => {}
+        }
+    }
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ast-find",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": rule_id,
+                        "name": "AstFindQuery",
+                        "shortDescription": { "text": query },
+                    }],
+                }
+            },
+            "invocations": [{
+                "executionSuccessful": notifications.is_empty(),
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }]
+    })
+}
+
+/// A relative URI for `path`: forward slashes, no leading `./`, and reserved
+/// characters percent-encoded.
+fn artifact_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    let mut uri = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_tools_common::ErrorCode;
+
+    fn sample_match(path: &str, line: u32) -> Event {
+        Event::Match {
+            lang: Some("python".to_string()),
+            path: path.to_string(),
+            start_line: line,
+            end_line: line + 1,
+            chunk_id: format!("chunk{line}"),
+            score: 1.0,
+            excerpt: Some("requests.get(url)\n".to_string()),
+            enclosing: None,
+            capture: json!({ "callee": "get" }),
+        }
+    }
+
+    #[test]
+    fn document_has_sarif_required_fields() {
+        let events = [
+            sample_match("./src/api client.py", 3),
+            sample_match("./src/main.py", 10),
+            Event::Error {
+                code: ErrorCode::Parse,
+                message: "cannot read".to_string(),
+                path_or_url: Some("./broken.py".to_string()),
+            },
+        ];
+        let query = "call(callee=/^get$/)";
+        let doc = document(query, &events);
+
+        // sarifLog: version and runs are required
+        assert_eq!(doc["version"], "2.1.0");
+        assert_eq!(doc["$schema"], SARIF_SCHEMA);
+        let run = &doc["runs"][0];
+        // run: tool.driver.name is required
+        assert_eq!(run["tool"]["driver"]["name"], "ast-find");
+        let rule = &run["tool"]["driver"]["rules"][0];
+        assert_eq!(rule["id"], rule_id(query));
+        assert!(rule["id"].as_str().unwrap().starts_with("call-"));
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        for result in results {
+            // result: message.text is required; ruleId ties it to the rule
+            assert_eq!(result["ruleId"], rule["id"]);
+            assert_eq!(result["message"]["text"], "requests.get(url)");
+            let location = &result["locations"][0]["physicalLocation"];
+            assert!(location["artifactLocation"]["uri"].is_string());
+            assert!(location["region"]["startLine"].as_u64().unwrap() >= 1);
+        }
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "src/api%20client.py"
+        );
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"]["endLine"],
+            11
+        );
+
+        let invocation = &run["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        assert_eq!(
+            invocation["toolExecutionNotifications"][0]["message"]["text"],
+            "cannot read"
+        );
+    }
+
+    #[test]
+    fn rule_id_is_stable_per_query() {
+        assert_eq!(rule_id("def(name=/x/)"), rule_id("def(name=/x/)"));
+        assert_ne!(rule_id("def(name=/x/)"), rule_id("def(name=/y/)"));
+        assert!(rule_id("def|call(text=/x/)").starts_with("def-"));
+    }
+}
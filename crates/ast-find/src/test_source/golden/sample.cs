@@ -0,0 +1,24 @@
+// Golden fixture for the C# adapter
+using System;
+using System.Collections.Generic;
+
+namespace Example
+{
+    public class Greeter
+    {
+        private readonly List<string> names = new List<string>();
+
+        public void Add(string name)
+        {
+            names.Add(name.Trim());
+        }
+
+        public void Greet()
+        {
+            foreach (var name in names)
+            {
+                Console.WriteLine($"Hello, {name}");
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// Golden fixture for the Go adapter
+package main
+
+import (
+	"fmt"
+	"net/http"
+)
+
+type Server struct {
+	addr string
+}
+
+func (s *Server) Start() error {
+	return http.ListenAndServe(s.addr, nil)
+}
+
+func main() {
+	s := &Server{addr: ":8080"}
+	fmt.Println("listening on", s.addr)
+	s.Start()
+}
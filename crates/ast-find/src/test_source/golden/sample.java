@@ -0,0 +1,18 @@
+// Golden fixture for the Java adapter
+package com.example;
+
+import java.util.List;
+import java.util.ArrayList;
+
+public class Inventory {
+    private final List<String> items = new ArrayList<>();
+
+    @Override
+    public String toString() {
+        return String.join(",", items);
+    }
+
+    public void add(String item) {
+        items.add(item.trim());
+    }
+}
@@ -0,0 +1,22 @@
+// Golden fixture for the JavaScript adapter
+import axios from 'axios';
+import { readFile as read } from 'fs/promises';
+
+export async function fetchUser(id) {
+  const res = await axios.get(`/users/${id}`);
+  return res.data;
+}
+
+const parse = (text) => JSON.parse(text);
+
+class Cache {
+  constructor() {
+    this.map = new Map();
+  }
+
+  lookup(key) {
+    return this.map.get(key);
+  }
+}
+
+read('config.json').then(parse);
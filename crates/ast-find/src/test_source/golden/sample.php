@@ -0,0 +1,20 @@
+<?php
+// Golden fixture for the PHP adapter
+namespace App;
+
+use App\Models\User;
+require_once 'config.php';
+
+class UserRepository
+{
+    public function find(int $id): ?User
+    {
+        $row = $this->db->fetch($id);
+        return User::fromRow(trim($row));
+    }
+}
+
+function load_users(UserRepository $repo): array
+{
+    return array_map(fn ($id) => $repo->find($id), range(1, 3));
+}
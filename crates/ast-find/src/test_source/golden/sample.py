@@ -0,0 +1,21 @@
+"""Golden fixture for the Python adapter."""
+import os
+import numpy as np
+from collections import OrderedDict
+
+
+class Store:
+    def __init__(self, path):
+        self.path = os.path.abspath(path)
+        self.items = OrderedDict()
+
+    @property
+    def size(self):
+        return len(self.items)
+
+
+def load(path):
+    store = Store(path)
+    data = np.loadtxt(path)
+    print(data.shape)
+    return store
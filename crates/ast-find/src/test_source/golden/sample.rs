@@ -0,0 +1,20 @@
+// Golden fixture for the Rust adapter
+use std::collections::HashMap;
+use std::fs;
+
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let values = text.lines().filter_map(parse_line).collect();
+        Ok(Config { values })
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim().to_string(), value.trim().to_string()))
+}
@@ -0,0 +1,19 @@
+// Golden fixture for the TypeScript adapter
+import type { Request } from 'express';
+import { createHash } from 'crypto';
+
+export interface User {
+  id: string;
+  name: string;
+}
+
+type UserId = User['id'];
+
+export function hashId(id: UserId): string {
+  return createHash('sha256').update(id).digest('hex');
+}
+
+export const handler = async (req: Request): Promise<User> => {
+  const id = hashId(req.params.id);
+  return { id, name: String(req.query.name) };
+};
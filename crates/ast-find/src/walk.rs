@@ -0,0 +1,391 @@
+/// Directory walking and per-file language detection for ast-find.
+use crate::languages::{detect_shebang, lang_for_path, mapped_lang, LangId};
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Options controlling which files the walker yields.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Restrict results to these languages (all when `None`).
+    pub lang_filter: Option<Vec<LangId>>,
+    /// Sniff `#!` lines of files without a recognized extension.
+    pub shebang: bool,
+    /// Directory levels to descend below each root (0 = only files directly
+    /// in it; unlimited when `None`).
+    pub max_depth: Option<usize>,
+    /// Search files excluded by `.gitignore`, `.ignore`, and the global
+    /// git excludes.
+    pub no_ignore: bool,
+    /// Skip hidden files and directories.
+    pub skip_hidden: bool,
+    /// Only yield files that `git diff` reports as changed since this revision.
+    pub changed_since: Option<String>,
+    /// Extra `(extension, language)` mappings, checked before the built-in
+    /// ones so they can also re-map a known extension.
+    pub extensions: Vec<(String, LangId)>,
+}
+
+/// Walk every root and return each searchable file with its language, sorted
+/// by path. A file reachable from more than one root is listed once, under the
+/// path that sorts first.
+pub fn collect_files(roots: &[&Path], opts: &WalkOptions) -> Result<Vec<(PathBuf, LangId)>> {
+    let Some((first, rest)) = roots.split_first() else {
+        return Ok(Vec::new());
+    };
+    let mut builder = WalkBuilder::new(first);
+    for root in rest {
+        builder.add(root);
+    }
+
+    let changed = match &opts.changed_since {
+        Some(rev) => Some(changed_files(roots, rev)?),
+        None => None,
+    };
+
+    let mut files = Vec::new();
+    let walker = builder
+        .hidden(opts.skip_hidden)
+        .git_ignore(!opts.no_ignore)
+        .git_global(!opts.no_ignore)
+        .git_exclude(!opts.no_ignore)
+        .ignore(!opts.no_ignore)
+        // `WalkBuilder` counts `root` itself as depth 0 and its files as 1
+        .max_depth(opts.max_depth.map(|depth| depth + 1))
+        .build();
+
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+            continue;
+        }
+
+        let Some(lang_id) = detect_lang(entry.path(), opts) else {
+            continue;
+        };
+
+        // Apply language filter
+        if let Some(ref filter) = opts.lang_filter {
+            if !filter.contains(&lang_id) {
+                continue;
+            }
+        }
+        if let Some(ref changed) = changed {
+            let path = std::fs::canonicalize(entry.path())?;
+            if !changed.contains(&path) {
+                continue;
+            }
+        }
+        files.push((entry.path().to_path_buf(), lang_id));
+    }
+
+    sort_unique(&mut files);
+    Ok(files)
+}
+
+/// Detect the language of each listed path without walking (for
+/// `--files-from`), applying the language filter as the walker would. Returns
+/// the searchable files, sorted and de-duplicated like [`collect_files`], and
+/// the paths whose language could not be determined.
+pub fn classify_files(
+    paths: impl IntoIterator<Item = PathBuf>,
+    opts: &WalkOptions,
+) -> (Vec<(PathBuf, LangId)>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut unknown = Vec::new();
+    for path in paths {
+        match detect_lang(&path, opts) {
+            Some(lang_id)
+                if opts
+                    .lang_filter
+                    .as_ref()
+                    .is_some_and(|f| !f.contains(&lang_id)) => {}
+            Some(lang_id) => files.push((path, lang_id)),
+            None => unknown.push(path),
+        }
+    }
+    sort_unique(&mut files);
+    unknown.sort();
+    unknown.dedup();
+    (files, unknown)
+}
+
+/// Sort files by path for deterministic output, keeping the first spelling of
+/// each: overlapping roots (`src` and `src/api`, or `.` and `src`) reach the
+/// same file under different paths.
+fn sort_unique(files: &mut Vec<(PathBuf, LangId)>) {
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut seen = HashSet::new();
+    files.retain(|(path, _)| seen.insert(std::fs::canonicalize(path).unwrap_or(path.clone())));
+}
+
+/// Canonical paths of the files changed between `rev` and the working tree in
+/// the git repositories containing `roots`. Deleted files are left out.
+fn changed_files(roots: &[&Path], rev: &str) -> Result<HashSet<PathBuf>> {
+    let mut toplevels = HashSet::new();
+    for root in roots {
+        let toplevel = git(root, &["rev-parse", "--show-toplevel"])?;
+        toplevels.insert(PathBuf::from(toplevel.trim_end()));
+    }
+
+    let mut changed = HashSet::new();
+    for toplevel in toplevels {
+        // -z keeps non-ASCII names unquoted; --end-of-options keeps a rev
+        // that starts with a dash from being read as an option
+        let names = git(
+            &toplevel,
+            &[
+                "diff",
+                "--name-only",
+                "-z",
+                "--no-renames",
+                "--diff-filter=d",
+                "--end-of-options",
+                rev,
+                "--",
+            ],
+        )?;
+        for name in names.split('\0').filter(|name| !name.is_empty()) {
+            // A file changed in the diff may since have been removed from disk
+            if let Ok(path) = std::fs::canonicalize(toplevel.join(name)) {
+                changed.insert(path);
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Run `git` in `dir` and return its stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("failed to run git (needed for --changed-since)")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn detect_lang(path: &Path, opts: &WalkOptions) -> Option<LangId> {
+    match mapped_lang(path, &opts.extensions).or_else(|| lang_for_path(path)) {
+        Some(lang_id) => Some(lang_id),
+        None if opts.shebang => detect_shebang(path),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensionless_python_script_detected_with_shebang() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("deploy"),
+            "#!/usr/bin/env python3\nprint('hi')\n",
+        )
+        .expect("write script");
+        std::fs::write(dir.path().join("README"), "just text\n").expect("write readme");
+
+        let files = collect_files(&[dir.path()], &WalkOptions::default()).expect("walk");
+        assert!(files.is_empty());
+
+        let opts = WalkOptions {
+            shebang: true,
+            ..WalkOptions::default()
+        };
+        let files = collect_files(&[dir.path()], &opts).expect("walk");
+        assert_eq!(files.len(), 1);
+        assert!(files[0].0.ends_with("deploy"));
+        assert_eq!(files[0].1, LangId::Python);
+    }
+
+    #[test]
+    fn mapped_extensions_are_searched() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        for name in ["App.vue", "view.tsx.snap", "other.snap", "util.js", "vue"] {
+            std::fs::write(dir.path().join(name), "x = 1\n").expect("write file");
+        }
+
+        let found = |extensions: Vec<(String, LangId)>| {
+            let opts = WalkOptions {
+                extensions,
+                ..WalkOptions::default()
+            };
+            collect_files(&[dir.path()], &opts)
+                .expect("walk")
+                .into_iter()
+                .map(|(path, lang)| {
+                    (
+                        path.file_name().unwrap().to_string_lossy().to_string(),
+                        lang,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            found(Vec::new()),
+            [("util.js".to_string(), LangId::JavaScript)]
+        );
+        let mappings = ["vue=javascript", ".tsx.snap=ts", "snap=py", "js=python"]
+            .into_iter()
+            .map(|spec| crate::languages::parse_ext_mapping(spec).unwrap())
+            .collect();
+        assert_eq!(
+            found(mappings),
+            [
+                ("App.vue".to_string(), LangId::JavaScript),
+                ("other.snap".to_string(), LangId::Python),
+                ("util.js".to_string(), LangId::Python),
+                ("view.tsx.snap".to_string(), LangId::TypeScript),
+            ]
+        );
+        assert!(crate::languages::parse_ext_mapping("vue=cobol").is_err());
+        assert!(crate::languages::parse_ext_mapping("vue").is_err());
+    }
+
+    #[test]
+    fn max_depth_limits_recursion() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let nested = dir.path().join("a/b");
+        std::fs::create_dir_all(&nested).expect("create dirs");
+        std::fs::write(dir.path().join("top.py"), "x = 1\n").expect("write top");
+        std::fs::write(dir.path().join("a/mid.py"), "x = 1\n").expect("write mid");
+        std::fs::write(nested.join("deep.py"), "x = 1\n").expect("write deep");
+
+        let names = |max_depth| {
+            let opts = WalkOptions {
+                max_depth,
+                ..WalkOptions::default()
+            };
+            collect_files(&[dir.path()], &opts)
+                .expect("walk")
+                .into_iter()
+                .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(Some(0)), ["top.py"]);
+        assert_eq!(names(Some(1)), ["mid.py", "top.py"]);
+        assert_eq!(names(None), ["deep.py", "mid.py", "top.py"]);
+    }
+
+    #[test]
+    fn no_ignore_includes_gitignored_files() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        // .gitignore only applies inside a git repository
+        std::fs::create_dir(dir.path().join(".git")).expect("create .git");
+        std::fs::write(dir.path().join(".gitignore"), "generated.py\n").expect("write ignore");
+        std::fs::write(dir.path().join("app.py"), "x = 1\n").expect("write app");
+        std::fs::write(dir.path().join("generated.py"), "x = 1\n").expect("write generated");
+        std::fs::write(dir.path().join(".hidden.py"), "x = 1\n").expect("write hidden");
+
+        let names = |opts: WalkOptions| {
+            collect_files(&[dir.path()], &opts)
+                .expect("walk")
+                .into_iter()
+                .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(WalkOptions::default()), [".hidden.py", "app.py"]);
+        let no_ignore = WalkOptions {
+            no_ignore: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(names(no_ignore), [".hidden.py", "app.py", "generated.py"]);
+        let skip_hidden = WalkOptions {
+            skip_hidden: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(names(skip_hidden), ["app.py"]);
+    }
+
+    #[test]
+    fn multiple_roots_are_merged_without_duplicates() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        for file in [
+            "src/api/routes.py",
+            "src/app.py",
+            "tests/test_app.py",
+            "scripts/x.py",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "pass\n").unwrap();
+        }
+        let (src, api, tests) = (
+            dir.path().join("src"),
+            dir.path().join("src/api"),
+            dir.path().join("tests"),
+        );
+
+        let files = collect_files(&[&tests, &api, &src], &WalkOptions::default()).expect("walk");
+        let names: Vec<_> = files
+            .iter()
+            .map(|(p, _)| p.strip_prefix(dir.path()).unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["src/api/routes.py", "src/app.py", "tests/test_app.py"]
+        );
+    }
+
+    #[test]
+    fn changed_since_keeps_only_modified_files() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let run_git = |args: &[&str]| git(dir.path(), args).expect("git");
+        run_git(&["init", "-q"]);
+        for name in ["committed.py", "modified.py", "deleted.py", "é.py"] {
+            std::fs::write(dir.path().join(name), "pass\n").unwrap();
+        }
+        run_git(&["add", "."]);
+        run_git(&[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-qm",
+            "init",
+        ]);
+        std::fs::write(dir.path().join("modified.py"), "print('changed')\n").unwrap();
+        std::fs::write(dir.path().join("é.py"), "print('changed')\n").unwrap();
+        std::fs::remove_file(dir.path().join("deleted.py")).unwrap();
+
+        let opts = WalkOptions {
+            changed_since: Some("HEAD".to_string()),
+            ..Default::default()
+        };
+        let files = collect_files(&[dir.path()], &opts).expect("walk");
+        assert_eq!(files.len(), 2);
+        assert!(files[0].0.ends_with("modified.py"));
+        assert!(files[1].0.ends_with("é.py"));
+
+        // A rev shaped like an option is still taken as a rev
+        let output = dir.path().join("written-by-git");
+        let opts = WalkOptions {
+            changed_since: Some(format!("--output={}", output.display())),
+            ..Default::default()
+        };
+        assert!(collect_files(&[dir.path()], &opts).is_err());
+        assert!(!output.exists());
+
+        let opts = WalkOptions {
+            changed_since: Some("no-such-ref".to_string()),
+            ..Default::default()
+        };
+        assert!(collect_files(&[dir.path()], &opts).is_err());
+    }
+}
@@ -1,6 +1,45 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Serialize;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Version of the NDJSON event schema, emitted as `"v"` on every line.
+///
+/// Bump this whenever an existing field changes meaning or is removed so that
+/// downstream parsers can branch on it. Purely additive fields do not require
+/// a bump.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Machine-readable category of an `Event::Error`, serialized as the
+/// canonical `E_*` string.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Network or HTTP failure.
+    #[serde(rename = "E_FETCH")]
+    Fetch,
+    /// A source file could not be read or parsed.
+    #[serde(rename = "E_PARSE")]
+    Parse,
+    /// The per-input deadline passed before the fetch finished.
+    #[serde(rename = "E_TIMEOUT")]
+    Timeout,
+    /// The worker handling an input crashed.
+    #[serde(rename = "E_PANIC")]
+    Panic,
+    /// A 200 response that reads like a not-found or login page.
+    #[serde(rename = "E_SOFT_404")]
+    Soft404,
+    /// Unsupported content type.
+    #[serde(rename = "E_MEDIA")]
+    Media,
+    /// The URL's host is denied by, or missing from, the host allow/deny lists.
+    #[serde(rename = "E_HOST_BLOCKED")]
+    HostBlocked,
+    /// The URL's host is or resolves to a private, loopback, or link-local address.
+    #[serde(rename = "E_SSRF_BLOCKED")]
+    SsrfBlocked,
+}
 
 /// NDJSON event skeleton shared across tools.
 #[derive(Serialize, Debug)]
@@ -15,6 +54,9 @@ pub enum Event {
         chunk_id: String,
         score: f32,
         excerpt: Option<String>,
+        /// Name of the innermost named function, method, or class around the
+        /// match; `None` at top level.
+        enclosing: Option<String>,
         capture: serde_json::Value,
     },
     #[serde(rename = "document")]
@@ -22,32 +64,250 @@ pub enum Event {
         url: String,
         title: String,
         byline: Option<String>,
+        /// From `<meta name="description">`, else `og:description`.
+        description: Option<String>,
         text_md: String,
         word_count: u32,
         links: Vec<String>,
         canonical_url: Option<String>,
         media_type: String,
         hash: String,
+        /// URLs that redirected (or, with `--follow-canonical`, canonicalized)
+        /// on the way to `url`, oldest first.
+        redirects: Vec<String>,
+        /// The response's `Content-Language` header, verbatim.
+        content_language: Option<String>,
+        /// ISO 639-1 code detected from `text_md`; null when inconclusive.
+        language: Option<String>,
+        /// Sanitized main-content HTML; only present with `--include-raw-html`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        raw_html: Option<String>,
     },
+    /// Stands in for a `Document` whose `hash` matches the previous run's
+    /// (`--skip-unchanged`).
+    #[serde(rename = "unchanged")]
+    Unchanged { url: String, hash: String },
     #[serde(rename = "error")]
     Error {
-        code: String,
+        code: ErrorCode,
         message: String,
         path_or_url: Option<String>,
     },
     #[serde(rename = "summary")]
-    Summary { tool: String, message: String },
+    Summary {
+        tool: String,
+        message: String,
+        /// Tool-specific structured totals; omitted when null.
+        #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+        stats: serde_json::Value,
+    },
+}
+
+/// JSON Schema (draft 2020-12) describing every NDJSON line either tool emits.
+///
+/// Hand-maintained alongside [`Event`]; keep the two in sync when fields change.
+pub fn event_schema() -> serde_json::Value {
+    use serde_json::json;
+
+    let string = json!({ "type": "string" });
+    let nullable_string = json!({ "type": ["string", "null"] });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let variant = |name: &str, required: &[&str], properties: serde_json::Value| {
+        let mut props = json!({
+            "v": { "const": SCHEMA_VERSION },
+            "type": { "const": name },
+        });
+        if let (Some(props), Some(extra)) = (props.as_object_mut(), properties.as_object()) {
+            props.extend(extra.clone());
+        }
+        let mut required: Vec<&str> = required.to_vec();
+        required.splice(0..0, ["v", "type"]);
+        json!({
+            "title": name,
+            "type": "object",
+            "required": required,
+            "properties": props,
+        })
+    };
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "agent-tools NDJSON event",
+        "oneOf": [
+            variant(
+                "match",
+                &["path", "start_line", "end_line", "chunk_id", "score", "capture"],
+                json!({
+                    "lang": nullable_string,
+                    "path": string,
+                    "start_line": { "type": "integer", "minimum": 1 },
+                    "end_line": { "type": "integer", "minimum": 1 },
+                    "chunk_id": string,
+                    "score": { "type": "number" },
+                    "excerpt": nullable_string,
+                    "enclosing": nullable_string,
+                    "capture": { "type": "object" },
+                }),
+            ),
+            variant(
+                "document",
+                &["url", "title", "text_md", "word_count", "links", "media_type", "hash", "redirects"],
+                json!({
+                    "url": string,
+                    "title": string,
+                    "byline": nullable_string,
+                    "description": nullable_string,
+                    "text_md": string,
+                    "word_count": { "type": "integer", "minimum": 0 },
+                    "links": strings,
+                    "canonical_url": nullable_string,
+                    "media_type": string,
+                    "hash": string,
+                    "redirects": strings,
+                    "content_language": nullable_string,
+                    "language": nullable_string,
+                    "raw_html": string,
+                }),
+            ),
+            variant(
+                "error",
+                &["code", "message"],
+                json!({
+                    "code": {
+                        "enum": ["E_FETCH", "E_PARSE", "E_TIMEOUT", "E_PANIC", "E_SOFT_404", "E_MEDIA", "E_HOST_BLOCKED", "E_SSRF_BLOCKED"],
+                    },
+                    "message": string,
+                    "path_or_url": nullable_string,
+                }),
+            ),
+            variant(
+                "summary",
+                &["tool", "message"],
+                json!({
+                    "tool": string,
+                    "message": string,
+                    "stats": { "type": "object" },
+                }),
+            ),
+            variant(
+                "unchanged",
+                &["url", "hash"],
+                json!({
+                    "url": string,
+                    "hash": string,
+                }),
+            ),
+        ],
+    })
+}
+
+/// Pretty-print [`event_schema`] followed by a newline, for `--print-schema`.
+pub fn write_schema_to<W: Write>(out: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &event_schema())?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Wrapper that injects the schema version alongside the flattened value.
+#[derive(Serialize)]
+struct Versioned<'a, T: Serialize> {
+    v: u32,
+    #[serde(flatten)]
+    value: &'a T,
 }
 
 /// Write a single JSON object as a line (NDJSON). Flushes immediately.
 pub fn write_ndjson<T: Serialize>(value: &T) -> Result<()> {
-    use std::io::{self, Write};
-    serde_json::to_writer(io::stdout(), value)?;
-    io::stdout().write_all(b"\n")?;
-    io::stdout().flush()?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    write_ndjson_to(&mut out, value)
+}
+
+/// Write a single versioned JSON object as a line to an arbitrary writer.
+///
+/// The line is serialized in memory first, so a value that fails to
+/// serialize writes nothing rather than a truncated line.
+pub fn write_ndjson_to<W: Write, T: Serialize>(out: &mut W, value: &T) -> Result<()> {
+    let line = Versioned {
+        v: SCHEMA_VERSION,
+        value,
+    };
+    let mut buf = serde_json::to_vec(&line)?;
+    buf.push(b'\n');
+    out.write_all(&buf)?;
+    out.flush()?;
     Ok(())
 }
 
+/// An output file that only appears once it is complete.
+///
+/// Writes go to a hidden temporary file next to `path`; [`AtomicFile::commit`]
+/// renames it into place. Dropping the file without committing (an error or a
+/// panic mid-run) deletes the temporary file and leaves `path` untouched.
+pub struct AtomicFile {
+    file: Option<std::io::BufWriter<std::fs::File>>,
+    temp: PathBuf,
+    path: PathBuf,
+    /// Set once the temporary file has been renamed into place.
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let name = path
+            .file_name()
+            .with_context(|| format!("not a file path: {}", path.display()))?;
+        let temp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let file = std::fs::File::create(&temp)
+            .with_context(|| format!("failed to create {}", temp.display()))?;
+        Ok(Self {
+            file: Some(std::io::BufWriter::new(file)),
+            temp,
+            path,
+            committed: false,
+        })
+    }
+
+    /// Flush everything written and move the file into place. If that
+    /// fails, the temporary file is deleted as on drop.
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(file) = self.file.take() {
+            let file = file.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&self.temp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.as_mut().expect("not committed").write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.as_mut().expect("not committed").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Close the writer first so the file can be removed everywhere
+        drop(self.file.take());
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
 /// Deterministic chunk id from path + line range.
 pub fn make_chunk_id(path: &Path, s: u32, e: u32) -> String {
     let mut hasher = blake3::Hasher::new();
@@ -59,10 +319,70 @@ pub fn make_chunk_id(path: &Path, s: u32, e: u32) -> String {
     hasher.finalize().to_hex().to_string()
 }
 
-/// Heuristic binary detector (small sample, NUL byte check).
+/// Deterministic chunk id from the matched source text, optionally scoped to a path.
+/// Unlike [`make_chunk_id`], the id survives edits that only shift line numbers.
+pub fn make_content_chunk_id(path: Option<&Path>, text: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    if let Some(path) = path {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(b":");
+    }
+    hasher.update(text.as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Heuristic binary detector over the first `sample_size` bytes of a buffer.
+///
+/// A buffer is binary when the sample contains a NUL byte (if `nul_check`)
+/// or when the share of control bytes other than common whitespace exceeds
+/// `max_non_text_ratio`. Bytes at or above 0x80 count as text, so UTF-8 and
+/// legacy single-byte encodings pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinaryDetector {
+    pub sample_size: usize,
+    pub nul_check: bool,
+    /// Fraction of non-text bytes (0.0–1.0) above which the sample is
+    /// binary; off when `None`.
+    pub max_non_text_ratio: Option<f64>,
+}
+
+impl Default for BinaryDetector {
+    /// What `is_probably_binary` uses: a NUL anywhere in the first 1 KiB.
+    fn default() -> Self {
+        Self {
+            sample_size: 1024,
+            nul_check: true,
+            max_non_text_ratio: None,
+        }
+    }
+}
+
+impl BinaryDetector {
+    pub fn is_binary(&self, buf: &[u8]) -> bool {
+        let sample = &buf[..buf.len().min(self.sample_size)];
+        if self.nul_check && sample.contains(&0) {
+            return true;
+        }
+        match self.max_non_text_ratio {
+            Some(max) if !sample.is_empty() => {
+                let non_text = sample.iter().filter(|&&b| !is_text_byte(b)).count();
+                non_text as f64 / sample.len() as f64 > max
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Printable ASCII, common whitespace and control codes seen in text
+/// (form feed, escape), or any byte of a non-ASCII character.
+fn is_text_byte(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b | 0x20..=0x7e | 0x80..)
+}
+
+/// Heuristic binary detector (small sample, NUL byte check); see
+/// `BinaryDetector` for other sample sizes and strategies.
 pub fn is_probably_binary(buf: &[u8]) -> bool {
-    const N: usize = 1024;
-    buf.iter().take(buf.len().min(N)).any(|&b| b == 0)
+    BinaryDetector::default().is_binary(buf)
 }
 
 /// Line index for fast line number to byte offset mapping.
@@ -111,17 +431,21 @@ impl LineIndex {
     }
 }
 
-/// Extract a slice of source with context lines around the target range.
-/// Returns a String with the excerpt (1-based line numbers).
+/// Extract a slice of source with `before` context lines above the target
+/// range and `after` below it.
+/// Returns a String with the excerpt (1-based line numbers); invalid UTF-8
+/// becomes U+FFFD rather than losing the excerpt. `None` only when the lines
+/// are out of range.
 pub fn slice_with_context(
     src: &[u8],
     line_index: &LineIndex,
     start_line: u32,
     end_line: u32,
-    context: u32,
+    before: u32,
+    after: u32,
 ) -> Option<String> {
-    let start_with_ctx = start_line.saturating_sub(context);
-    let end_with_ctx = end_line.saturating_add(context);
+    let start_with_ctx = start_line.saturating_sub(before);
+    let end_with_ctx = end_line.saturating_add(after);
 
     let (start_byte, end_byte) = line_index.line_range_to_bytes(start_with_ctx, end_with_ctx)?;
 
@@ -131,5 +455,379 @@ pub fn slice_with_context(
         &src[start_byte..end_byte.min(src.len())]
     };
 
-    String::from_utf8(slice.to_vec()).ok()
+    Some(String::from_utf8_lossy(slice).into_owned())
+}
+
+/// Parse a human-readable size string (e.g., "10MB", "1.5GiB").
+///
+/// `KiB`/`MiB`/`GiB` are always powers of 1024. `KB`/`MB`/`GB` are also
+/// powers of 1024 unless `si` is set, in which case they are powers of 1000.
+pub fn parse_size(s: &str, si: bool) -> Result<usize> {
+    let s = s.trim().to_uppercase();
+    let (num_part, unit) = if let Some(pos) = s.find(|c: char| c.is_alphabetic()) {
+        (&s[..pos], &s[pos..])
+    } else {
+        (s.as_str(), "")
+    };
+
+    let num = parse_number(num_part).with_context(|| format!("invalid size: {:?}", s))?;
+    let decimal_base = if si { 1000 } else { 1024 };
+    let multiplier: usize = match unit {
+        "B" | "" => 1,
+        "KIB" => 1024,
+        "MIB" => 1024 * 1024,
+        "GIB" => 1024 * 1024 * 1024,
+        "KB" => decimal_base,
+        "MB" => decimal_base.pow(2),
+        "GB" => decimal_base.pow(3),
+        _ => anyhow::bail!("Unknown size unit: {}", unit),
+    };
+
+    Ok((num * multiplier as f64).round() as usize)
+}
+
+/// Parse a human-readable duration string (e.g., "15s", "500ms", "1m30s").
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim().to_lowercase();
+    if s.is_empty() {
+        anyhow::bail!("empty duration");
+    }
+    let too_long = || format!("duration too long: {:?}", s);
+    if let Ok(secs) = parse_number(&s) {
+        return Duration::try_from_secs_f64(secs).with_context(too_long);
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = s.as_str();
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let unit_end = rest[num_end..]
+            .find(|c: char| !c.is_alphabetic())
+            .map_or(rest.len(), |i| num_end + i);
+        let (num_part, unit) = (&rest[..num_end], &rest[num_end..unit_end]);
+
+        let num = parse_number(num_part).with_context(|| format!("invalid duration: {:?}", s))?;
+        let secs = match unit {
+            "ms" => num / 1000.0,
+            "s" | "sec" => num,
+            "m" | "min" => num * 60.0,
+            "h" | "hour" => num * 3600.0,
+            _ => anyhow::bail!("Unknown duration unit: {}", unit),
+        };
+        total = Duration::try_from_secs_f64(secs)
+            .ok()
+            .and_then(|d| total.checked_add(d))
+            .with_context(too_long)?;
+        rest = &rest[unit_end..];
+    }
+
+    Ok(total)
+}
+
+/// A non-negative decimal such as `15` or `1.5`.
+fn parse_number(s: &str) -> Result<f64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        anyhow::bail!("expected a number, got {:?}", s);
+    }
+    Ok(s.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emit(event: &Event) -> serde_json::Value {
+        let mut buf = Vec::new();
+        write_ndjson_to(&mut buf, event).unwrap();
+        assert!(buf.ends_with(b"\n"));
+        serde_json::from_slice(&buf).unwrap()
+    }
+
+    #[test]
+    fn binary_detector_sample_size_and_ratio() {
+        let mut late_nul = b"plain text\n".repeat(200);
+        late_nul.push(0);
+        assert!(!is_probably_binary(&late_nul));
+        let wide = BinaryDetector {
+            sample_size: 8192,
+            ..Default::default()
+        };
+        assert!(wide.is_binary(&late_nul));
+
+        let controls: Vec<u8> = (0..512u32).map(|i| (i % 31 + 1) as u8).collect();
+        assert!(!is_probably_binary(&controls));
+        let ratio = BinaryDetector {
+            max_non_text_ratio: Some(0.3),
+            ..Default::default()
+        };
+        assert!(ratio.is_binary(&controls));
+
+        // Text with one stray NUL passes once the NUL check is off
+        let mut stray = b"\0".to_vec();
+        stray.extend_from_slice("caf\u{e9} au lait\n".repeat(20).as_bytes());
+        assert!(is_probably_binary(&stray));
+        let lenient = BinaryDetector {
+            nul_check: false,
+            max_non_text_ratio: Some(0.3),
+            ..Default::default()
+        };
+        assert!(!lenient.is_binary(&stray));
+        assert!(!lenient.is_binary(b""));
+    }
+
+    #[test]
+    fn match_event_carries_schema_version() {
+        let value = emit(&Event::Match {
+            lang: Some("rust".to_string()),
+            path: "src/lib.rs".to_string(),
+            start_line: 1,
+            end_line: 2,
+            chunk_id: "abc".to_string(),
+            score: 1.0,
+            excerpt: None,
+            enclosing: Some("main".to_string()),
+            capture: serde_json::json!({}),
+        });
+        assert_eq!(value["v"], SCHEMA_VERSION);
+        assert_eq!(value["type"], "match");
+        assert_eq!(value["path"], "src/lib.rs");
+        assert_eq!(value["enclosing"], "main");
+    }
+
+    #[test]
+    fn document_event_carries_schema_version() {
+        let value = emit(&Event::Document {
+            url: "https://example.com".to_string(),
+            title: "Example".to_string(),
+            byline: None,
+            description: None,
+            text_md: "# Example".to_string(),
+            word_count: 2,
+            links: vec![],
+            canonical_url: None,
+            media_type: "text/html".to_string(),
+            hash: "def".to_string(),
+            redirects: vec![],
+            content_language: None,
+            language: Some("en".to_string()),
+            raw_html: None,
+        });
+        assert_eq!(value["v"], SCHEMA_VERSION);
+        assert_eq!(value["type"], "document");
+        assert_eq!(value["title"], "Example");
+    }
+
+    #[test]
+    fn error_codes_serialize_to_canonical_strings() {
+        let cases = [
+            (ErrorCode::Fetch, "E_FETCH"),
+            (ErrorCode::Parse, "E_PARSE"),
+            (ErrorCode::Timeout, "E_TIMEOUT"),
+            (ErrorCode::Panic, "E_PANIC"),
+            (ErrorCode::Soft404, "E_SOFT_404"),
+            (ErrorCode::Media, "E_MEDIA"),
+            (ErrorCode::HostBlocked, "E_HOST_BLOCKED"),
+            (ErrorCode::SsrfBlocked, "E_SSRF_BLOCKED"),
+        ];
+        for (code, expected) in cases {
+            let value = emit(&Event::Error {
+                code,
+                message: "boom".to_string(),
+                path_or_url: None,
+            });
+            assert_eq!(value["type"], "error");
+            assert_eq!(value["code"], expected);
+        }
+    }
+
+    #[test]
+    fn failed_serialization_writes_nothing() {
+        /// Serializes one field, then fails.
+        struct Broken;
+        impl Serialize for Broken {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::{Error, SerializeMap};
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "match")?;
+                Err(S::Error::custom("cannot serialize"))
+            }
+        }
+
+        let mut buf = Vec::new();
+        write_ndjson_to(
+            &mut buf,
+            &Event::Summary {
+                tool: "test".to_string(),
+                message: "before".to_string(),
+                stats: serde_json::Value::Null,
+            },
+        )
+        .unwrap();
+        let before = buf.len();
+        assert!(write_ndjson_to(&mut buf, &Broken).is_err());
+        assert_eq!(buf.len(), before);
+    }
+
+    #[test]
+    fn atomic_file_appears_only_on_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.ndjson");
+        let summary = |message: &str| Event::Summary {
+            tool: "test".to_string(),
+            message: message.to_string(),
+            stats: serde_json::Value::Null,
+        };
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        for message in ["one", "two", "three"] {
+            write_ndjson_to(&mut file, &summary(message)).unwrap();
+        }
+        assert!(!path.exists());
+        file.commit().unwrap();
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2]["message"], "three");
+
+        // A run that aborts before committing leaves the old file alone and
+        // no temporary file behind
+        let mut file = AtomicFile::create(&path).unwrap();
+        write_ndjson_to(&mut file, &summary("partial")).unwrap();
+        drop(file);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("three"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let fresh = dir.path().join("fresh.ndjson");
+        let file = AtomicFile::create(&fresh).unwrap();
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let mut file = file;
+            write_ndjson_to(&mut file, &summary("partial")).unwrap();
+            panic!("abort mid-run");
+        }))
+        .unwrap_err();
+        assert!(!fresh.exists());
+    }
+
+    #[test]
+    fn failed_commit_removes_the_temporary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // A non-empty directory where the file should go makes the rename fail
+        let path = dir.path().join("out.ndjson");
+        std::fs::create_dir(&path).unwrap();
+        std::fs::write(path.join("keep"), "").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"data\n").unwrap();
+        assert!(file.commit().is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn printed_schema_lists_every_event_type() {
+        let mut buf = Vec::new();
+        write_schema_to(&mut buf).unwrap();
+        let schema: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+
+        let types: Vec<&str> = schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant["properties"]["type"]["const"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["match", "document", "error", "summary", "unchanged"]
+        );
+
+        let codes = &schema["oneOf"][2]["properties"]["code"]["enum"];
+        for code in [ErrorCode::Fetch, ErrorCode::Soft404, ErrorCode::Media] {
+            let code = serde_json::to_value(code).unwrap();
+            assert!(codes.as_array().unwrap().contains(&code));
+        }
+    }
+
+    #[test]
+    fn content_chunk_id_ignores_line_numbers() {
+        let path = Path::new("src/lib.rs");
+        let a = make_content_chunk_id(Some(path), "foo(1)");
+        let b = make_content_chunk_id(Some(path), "foo(1)");
+        assert_eq!(a, b);
+        assert_ne!(a, make_content_chunk_id(None, "foo(1)"));
+        assert_ne!(a, make_content_chunk_id(Some(path), "foo(2)"));
+        assert_ne!(make_chunk_id(path, 1, 1), make_chunk_id(path, 2, 2));
+    }
+
+    #[test]
+    fn excerpt_survives_invalid_utf8() {
+        let src = b"# caf\xe9 menu\nfetch(url)\nlast \xff\xfe line\n";
+        let index = LineIndex::new(src);
+
+        let excerpt = slice_with_context(src, &index, 2, 2, 1, 1).expect("excerpt");
+        assert_eq!(
+            excerpt,
+            "# caf\u{FFFD} menu\nfetch(url)\nlast \u{FFFD}\u{FFFD} line\n"
+        );
+        assert_eq!(
+            slice_with_context(src, &index, 2, 2, 0, 0).as_deref(),
+            Some("fetch(url)\n")
+        );
+        assert_eq!(slice_with_context(src, &index, 9, 9, 0, 0), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024", false).unwrap(), 1024);
+        assert_eq!(parse_size("1KB", false).unwrap(), 1024);
+        assert_eq!(parse_size("10MB", false).unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("1.5MB", false).unwrap(), 1572864);
+    }
+
+    #[test]
+    fn test_parse_size_units() {
+        // Binary units mean the same thing either way
+        for si in [false, true] {
+            assert_eq!(parse_size("1KiB", si).unwrap(), 1024);
+            assert_eq!(parse_size("2MiB", si).unwrap(), 2 * 1024 * 1024);
+            assert_eq!(parse_size("1GiB", si).unwrap(), 1024 * 1024 * 1024);
+        }
+        assert_eq!(parse_size("1KB", true).unwrap(), 1000);
+        assert_eq!(parse_size("1.5MB", true).unwrap(), 1_500_000);
+        assert_eq!(parse_size("1GB", true).unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("15s").unwrap(), Duration::from_secs(15));
+        assert_eq!(parse_duration("1m").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1m30s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("20").unwrap(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_parse_rejects_nonsense() {
+        assert!(parse_size("5xz", false).is_err());
+        assert!(parse_size("MB", false).is_err());
+        assert!(parse_duration("5xz").is_err());
+        assert!(parse_duration("1m30").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_overflow() {
+        for huge in [
+            "99999999999999999999",
+            "99999999999999999999h",
+            "18446744073709551615s1s",
+        ] {
+            let err = parse_duration(huge).unwrap_err();
+            assert!(err.to_string().contains("too long"), "{huge}: {err}");
+        }
+    }
 }
@@ -1,15 +1,58 @@
 /// HTML to Markdown conversion with sanitization.
 use ammonia::Builder;
 use anyhow::Result;
+use regex::Regex;
 use scraper::{Html, Selector};
 use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// How links are written in the converted Markdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LinkStyle {
+    /// `[text](url)` where the link appears.
+    #[default]
+    Inline,
+    /// `[text][n]`, with numbered `[n]: url` definitions at the end.
+    Reference,
+}
+
+/// Inline tags `--keep-tags` may add to the allowlist: presentational
+/// markup with no attributes worth keeping and nothing to execute.
+pub const KEEPABLE_TAGS: &[&str] = &["sup", "sub", "kbd", "mark", "del", "ins"];
+
+/// Parse a `--keep-tags` list such as `sup,sub,kbd`, rejecting any tag
+/// outside `KEEPABLE_TAGS`.
+pub fn parse_keep_tags(spec: &str) -> Result<Vec<String>> {
+    let mut tags = Vec::new();
+    for tag in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        let tag = tag.to_ascii_lowercase();
+        if !KEEPABLE_TAGS.contains(&tag.as_str()) {
+            anyhow::bail!(
+                "cannot keep <{}> (expected some of: {})",
+                tag,
+                KEEPABLE_TAGS.join(", ")
+            );
+        }
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
 
 pub struct ConvertOptions {
     pub keep_images: bool,
+    /// Extra inline tags from `KEEPABLE_TAGS` to let through sanitization.
+    pub keep_tags: Vec<String>,
     pub base_url: String,
+    pub link_style: LinkStyle,
+    /// Canonicalize each entry of `links` with `normalize_url`.
+    pub normalize_urls: bool,
 }
 
 pub struct ConvertedContent {
+    /// The sanitized HTML the Markdown was converted from.
+    pub html: String,
     pub markdown: String,
     pub links: Vec<String>,
     pub hash: String,
@@ -17,27 +60,325 @@ pub struct ConvertedContent {
 
 pub fn convert_to_markdown(html: &str, opts: &ConvertOptions) -> Result<ConvertedContent> {
     // Sanitize HTML
-    let sanitized = sanitize_html(html, &opts.base_url, opts.keep_images);
+    let sanitized = sanitize_html(html, &opts.base_url, opts.keep_images, &opts.keep_tags);
 
     // Extract links
-    let links = extract_links(&sanitized, &opts.base_url);
+    let links = extract_links(&sanitized, &opts.base_url, opts.normalize_urls);
 
     // Convert to Markdown
-    let markdown = html2md::parse_html(&sanitized);
+    let mut markdown = normalize_markdown(&normalize_text(&html2md::parse_html(
+        &markdown_friendly(&sanitized),
+    )));
+    if opts.link_style == LinkStyle::Reference {
+        markdown = reference_links(&markdown);
+    }
 
-    // Hash the markdown
+    // Hash the markdown, after normalization so equivalent spellings agree
     let hash = blake3::hash(markdown.as_bytes()).to_hex().to_string();
 
     Ok(ConvertedContent {
+        html: sanitized,
         markdown,
         links,
         hash,
     })
 }
 
-fn sanitize_html(html: &str, base_url: &str, keep_images: bool) -> String {
+/// Rewrite inline `[text](url)` links as `[text][n]`, numbering URLs by
+/// first appearance and appending `[n]: url` definitions. Images and fenced
+/// code are left as they are.
+fn reference_links(markdown: &str) -> String {
+    static INLINE_LINK: OnceLock<Regex> = OnceLock::new();
+    let inline_link = INLINE_LINK
+        .get_or_init(|| Regex::new(r#"(!?)\[([^\]]*)\]\(([^()\s]+)(?:\s+"[^"]*")?\)"#).unwrap());
+
+    let mut urls: Vec<String> = Vec::new();
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.trim_start().starts_with("```") {
+            lines.push(line.to_string());
+            continue;
+        }
+        let line = inline_link.replace_all(line, |caps: &regex::Captures| {
+            if !caps[1].is_empty() {
+                return caps[0].to_string();
+            }
+            let url = &caps[3];
+            let n = match urls.iter().position(|u| u == url) {
+                Some(i) => i + 1,
+                None => {
+                    urls.push(url.to_string());
+                    urls.len()
+                }
+            };
+            format!("[{}][{n}]", &caps[2])
+        });
+        lines.push(line.into_owned());
+    }
+
+    let mut out = lines.join("\n");
+    if !urls.is_empty() {
+        out.push_str("\n\n");
+        let definitions: Vec<String> = urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| format!("[{}]: {url}", i + 1))
+            .collect();
+        out.push_str(&definitions.join("\n"));
+    }
+    out
+}
+
+/// Named entities decoded by `normalize_text`: the ones html2md passes
+/// through in practice, not the full HTML table.
+const NAMED_ENTITIES: &[(&str, &str)] = &[
+    ("nbsp", " "),
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("ndash", "\u{2013}"),
+    ("mdash", "\u{2014}"),
+    ("hellip", "\u{2026}"),
+    ("lsquo", "\u{2018}"),
+    ("rsquo", "\u{2019}"),
+    ("ldquo", "\u{201C}"),
+    ("rdquo", "\u{201D}"),
+    ("laquo", "\u{00AB}"),
+    ("raquo", "\u{00BB}"),
+    ("middot", "\u{00B7}"),
+    ("bull", "\u{2022}"),
+    ("copy", "\u{00A9}"),
+    ("reg", "\u{00AE}"),
+    ("trade", "\u{2122}"),
+    ("deg", "\u{00B0}"),
+    ("times", "\u{00D7}"),
+    ("euro", "\u{20AC}"),
+    ("pound", "\u{00A3}"),
+    ("shy", ""),
+    ("zwnj", ""),
+    ("zwj", ""),
+];
+
+/// Decode the HTML entities html2md leaves in its output and turn
+/// non-breaking spaces into plain ones, so `word_count`, `hash` and search
+/// see the text a reader would. These are mostly double-escaped entities
+/// (`&amp;nbsp;` in the source) and non-breaking spaces in headings, tables
+/// and inline markup. Entities are decoded in one pass; unknown ones and
+/// fenced code are left as they are.
+fn normalize_text(markdown: &str) -> String {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    let entity = ENTITY.get_or_init(|| {
+        Regex::new(r"&(?:#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6})|([a-zA-Z]+));").unwrap()
+    });
+    let decode = |caps: &regex::Captures| -> Option<String> {
+        let code = match (caps.get(1), caps.get(2), caps.get(3)) {
+            (Some(dec), _, _) => dec.as_str().parse().ok()?,
+            (_, Some(hex), _) => u32::from_str_radix(hex.as_str(), 16).ok()?,
+            (_, _, Some(name)) => {
+                let (_, text) = NAMED_ENTITIES.iter().find(|(n, _)| *n == name.as_str())?;
+                return Some(text.to_string());
+            }
+            _ => return None,
+        };
+        match char::from_u32(code)? {
+            '\u{00A0}' => Some(" ".to_string()),
+            '\0' => None,
+            ch => Some(ch.to_string()),
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let fence = line.trim_start().starts_with("```");
+        if fence {
+            in_fence = !in_fence;
+        }
+        if in_fence || fence {
+            lines.push(line.to_string());
+            continue;
+        }
+        let line = entity.replace_all(line, |caps: &regex::Captures| {
+            decode(caps).unwrap_or_else(|| caps[0].to_string())
+        });
+        lines.push(line.replace('\u{00A0}', " "));
+    }
+    lines.join("\n")
+}
+
+/// Tidy list and blockquote output from html2md.
+///
+/// Nested list items are re-indented to two spaces per level (html2md indents
+/// ordered lists by marker width), the blank line html2md leaves after a
+/// nested list is dropped so the outer list continues, and runs of empty `>`
+/// lines inside blockquotes collapse to a single separator. Fenced code is
+/// left untouched.
+fn normalize_markdown(markdown: &str) -> String {
+    static LIST_ITEM: OnceLock<Regex> = OnceLock::new();
+    let list_item = LIST_ITEM.get_or_init(|| Regex::new(r"^( *)([*+-]|\d+\.) +(.*)$").unwrap());
+    let is_quote_blank =
+        |line: &str| line.starts_with('>') && line.trim_matches(['>', ' ']).is_empty();
+
+    let mut out: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    // Indent widths of the open list levels, outermost first
+    let mut indents: Vec<usize> = Vec::new();
+    let mut last_item_indent: Option<usize> = None;
+    let mut pending_blank = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.trim_start().starts_with("```") {
+            if pending_blank {
+                out.push(String::new());
+                pending_blank = false;
+            }
+            out.push(line.to_string());
+            indents.clear();
+            last_item_indent = None;
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            pending_blank = true;
+            continue;
+        }
+
+        if let Some(caps) = list_item.captures(line) {
+            let indent = caps[1].len();
+            // A shallower item after a blank line closes a nested list: same outer list
+            let continues_outer = last_item_indent.is_some_and(|last| indent < last);
+            if pending_blank && !continues_outer {
+                out.push(String::new());
+                indents.clear();
+            }
+            pending_blank = false;
+
+            while indents.last().is_some_and(|&top| top > indent) {
+                indents.pop();
+            }
+            if indents.last().is_none_or(|&top| top < indent) {
+                indents.push(indent);
+            }
+            let level = indents.len() - 1;
+            out.push(format!("{}{} {}", "  ".repeat(level), &caps[2], &caps[3]));
+            last_item_indent = Some(indent);
+            continue;
+        }
+
+        if is_quote_blank(line) {
+            // Leading separators and repeats carry no structure
+            let after_quote_text = out
+                .last()
+                .is_some_and(|prev| prev.starts_with('>') && !is_quote_blank(prev));
+            if after_quote_text && !pending_blank {
+                out.push(">".to_string());
+            }
+            continue;
+        }
+
+        if pending_blank {
+            out.push(String::new());
+            pending_blank = false;
+        }
+        if !line.starts_with('>') {
+            indents.clear();
+            last_item_indent = None;
+        }
+        out.push(line.to_string());
+    }
+
+    // Drop leading blank lines and separators left at the end of a blockquote
+    let mut result: Vec<String> = Vec::with_capacity(out.len());
+    for (i, line) in out.iter().enumerate() {
+        if line.is_empty() && result.is_empty() {
+            continue;
+        }
+        let next_is_quote_text = out
+            .get(i + 1)
+            .is_some_and(|next| next.starts_with('>') && !is_quote_blank(next));
+        if line == ">" && !next_is_quote_text {
+            continue;
+        }
+        result.push(line.clone());
+    }
+    result.join("\n")
+}
+
+/// Count prose words in Markdown, skipping fenced code blocks, link and image
+/// targets, and tokens that are pure syntax (`#`, `*`, `|`, `>`, list numbers).
+pub fn count_prose_words(md: &str) -> u32 {
+    static LINK_TARGET: OnceLock<Regex> = OnceLock::new();
+    let link_target = LINK_TARGET.get_or_init(|| Regex::new(r"\]\([^)]*\)").unwrap());
+
+    let mut in_fence = false;
+    let mut count = 0;
+    for line in md.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let line = link_target.replace_all(line, "]");
+        count += line
+            .split_whitespace()
+            .filter(|token| token.chars().any(char::is_alphabetic))
+            .count() as u32;
+    }
+    count
+}
+
+/// A YAML frontmatter block for `--frontmatter`, ending in a blank line so
+/// the Markdown body can follow directly. Strings are written as JSON, which
+/// is valid double-quoted YAML, so quotes, colons and newlines are escaped and
+/// missing values are `null`.
+pub fn frontmatter(fields: &[(&str, serde_json::Value)]) -> String {
+    let mut out = String::from("---\n");
+    for (key, value) in fields {
+        out.push_str(&format!("{key}: {value}\n"));
+    }
+    out.push_str("---\n\n");
+    out
+}
+
+/// Map kept tags html2md would flatten to ones it renders: `<kbd>` becomes
+/// inline code. `<sup>`/`<sub>` already pass through as inline HTML and
+/// `<del>` as `~~strikethrough~~`; `<mark>` and `<ins>` have no Markdown form
+/// and keep only their text (they remain in `raw_html`). Sanitized markup
+/// has no attributes on these tags, so plain replacement is enough.
+fn markdown_friendly(sanitized: &str) -> std::borrow::Cow<'_, str> {
+    if !sanitized.contains("<kbd>") {
+        return std::borrow::Cow::Borrowed(sanitized);
+    }
+    std::borrow::Cow::Owned(
+        sanitized
+            .replace("<kbd>", "<code>")
+            .replace("</kbd>", "</code>"),
+    )
+}
+
+fn sanitize_html(html: &str, base_url: &str, keep_images: bool, keep_tags: &[String]) -> String {
     let mut builder = Builder::default();
 
+    // Lazy-load and responsive images only carry their URL in attributes
+    // ammonia drops, so settle on one absolute `src` first
+    let html = if keep_images {
+        resolve_image_sources(html, base_url)
+    } else {
+        html.to_string()
+    };
+
     // Configure allowed tags
     let mut tags = maplit::hashset! {
         "p", "br", "h1", "h2", "h3", "h4", "h5", "h6",
@@ -49,6 +390,11 @@ fn sanitize_html(html: &str, base_url: &str, keep_images: bool) -> String {
     if keep_images {
         tags.insert("img");
     }
+    tags.extend(
+        KEEPABLE_TAGS
+            .iter()
+            .filter(|tag| keep_tags.iter().any(|keep| keep == *tag)),
+    );
 
     builder.tags(tags);
 
@@ -58,10 +404,101 @@ fn sanitize_html(html: &str, base_url: &str, keep_images: bool) -> String {
         builder.url_relative(ammonia::UrlRelative::RewriteWithBase(base));
     }
 
-    builder.clean(html).to_string()
+    builder.clean(&html).to_string()
+}
+
+/// Rewrite every `<img>` to a plain `<img src alt>` whose `src` is absolute.
+///
+/// The URL comes from `data-src`, then `src` (unless it is an inline `data:`
+/// placeholder), then the first `srcset` candidate. Images with none of these
+/// are dropped.
+fn resolve_image_sources(html: &str, base_url: &str) -> String {
+    static IMG_TAG: OnceLock<Regex> = OnceLock::new();
+    let img_tag = IMG_TAG.get_or_init(|| Regex::new(r"(?i)<img\b[^>]*>").unwrap());
+    let Ok(base) = url::Url::parse(base_url) else {
+        return html.to_string();
+    };
+    let img_selector = Selector::parse("img").unwrap();
+
+    img_tag
+        .replace_all(html, |caps: &regex::Captures| {
+            let fragment = Html::parse_fragment(&caps[0]);
+            let Some(img) = fragment.select(&img_selector).next() else {
+                return String::new();
+            };
+            let attr = |name| {
+                img.value()
+                    .attr(name)
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+            };
+
+            let src = attr("data-src")
+                .or_else(|| attr("src").filter(|v| !v.starts_with("data:")))
+                .or_else(|| {
+                    attr("srcset").and_then(|set| set.split(',').next()?.split_whitespace().next())
+                });
+            let Some(resolved) = src.and_then(|src| base.join(src).ok()) else {
+                return String::new();
+            };
+
+            format!(
+                r#"<img src="{}" alt="{}">"#,
+                escape_attr(resolved.as_str()),
+                escape_attr(attr("alt").unwrap_or_default())
+            )
+        })
+        .into_owned()
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// Query parameters `normalize_url` drops, besides any `utm_*` one: click
+/// identifiers that only track where a visitor came from.
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid"];
+
+/// Canonical spelling of a link for `--normalize-urls`: host lowercased,
+/// default port and fragment dropped, and `utm_*`, `fbclid` and `gclid`
+/// query parameters removed (the rest keep their order). Unparseable input
+/// comes back unchanged.
+pub fn normalize_url(link: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(link) else {
+        return link.to_string();
+    };
+    parsed.set_fragment(None);
+    if let Some(query) = parsed.query() {
+        // Segments are kept verbatim, so untouched parameters keep their
+        // encoding and valueless ones stay valueless
+        let segments: Vec<&str> = query.split('&').collect();
+        let kept: Vec<&str> = segments
+            .iter()
+            .copied()
+            .filter(|segment| !is_tracking_param(segment))
+            .collect();
+        if kept.len() < segments.len() {
+            let query = kept.join("&");
+            parsed.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+    }
+    // Parsing already lowercased the host and dropped a default port
+    parsed.to_string()
+}
+
+/// Whether a raw `key=value` query segment is a tracking parameter.
+fn is_tracking_param(segment: &str) -> bool {
+    let Some((key, _)) = url::form_urlencoded::parse(segment.as_bytes()).next() else {
+        return false;
+    };
+    let key = key.to_ascii_lowercase();
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key.as_str())
 }
 
-fn extract_links(html: &str, base_url: &str) -> Vec<String> {
+fn extract_links(html: &str, base_url: &str, normalize: bool) -> Vec<String> {
     let document = Html::parse_document(html);
     let mut links = HashSet::new();
 
@@ -83,7 +520,11 @@ fn extract_links(html: &str, base_url: &str) -> Vec<String> {
 
                 // Filter out non-http(s) links
                 if absolute.starts_with("http://") || absolute.starts_with("https://") {
-                    links.insert(absolute);
+                    links.insert(if normalize {
+                        normalize_url(&absolute)
+                    } else {
+                        absolute
+                    });
                 }
             }
         }
@@ -121,3 +562,210 @@ pub fn parse_charset(content_type: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        convert_with(html, LinkStyle::Inline).markdown
+    }
+
+    fn convert_with(html: &str, link_style: LinkStyle) -> ConvertedContent {
+        let opts = ConvertOptions {
+            keep_images: false,
+            keep_tags: Vec::new(),
+            base_url: "https://example.com/".to_string(),
+            link_style,
+            normalize_urls: false,
+        };
+        convert_to_markdown(html, &opts).unwrap()
+    }
+
+    #[test]
+    fn keep_tags_extends_the_allowlist() {
+        let html = "<p>E = mc<sup>2</sup>, press <kbd>Ctrl</kbd>+<kbd>C</kbd>, \
+                    <del>old</del> <mark>new</mark></p>";
+        let md = convert(html);
+        assert!(md.contains("E = mc2, press Ctrl+C"), "{md:?}");
+        assert!(!md.contains("<sup>"), "{md:?}");
+
+        let opts = ConvertOptions {
+            keep_images: false,
+            keep_tags: parse_keep_tags("sup, kbd,del,mark").unwrap(),
+            base_url: "https://example.com/".to_string(),
+            link_style: LinkStyle::Inline,
+            normalize_urls: false,
+        };
+        let converted = convert_to_markdown(html, &opts).unwrap();
+        assert!(
+            converted
+                .markdown
+                .contains("E = mc<sup>2</sup>, press `Ctrl`+`C`, ~~old~~ new"),
+            "{:?}",
+            converted.markdown
+        );
+        assert!(
+            converted.html.contains("<mark>new</mark>"),
+            "{}",
+            converted.html
+        );
+        assert!(
+            converted.html.contains("<kbd>Ctrl</kbd>"),
+            "{}",
+            converted.html
+        );
+
+        assert!(parse_keep_tags("sup,script").is_err());
+        assert_eq!(parse_keep_tags("SUP,sup,").unwrap(), ["sup"]);
+    }
+
+    #[test]
+    fn entities_and_nbsp_are_normalized() {
+        // Double-escaped entities, as some CMSes emit them, survive html2md
+        let html = "<article><h1>Fish&nbsp;&amp;&nbsp;Chips</h1>\
+                    <p>Open&amp;nbsp;daily &amp;amp; late&amp;#8230; Tom&amp;#x2019;s&amp;bogus;</p>\
+                    <table><tr><td>a&nbsp;b</td></tr></table>\
+                    <pre><code>x = &quot;&amp;nbsp;&quot;</code></pre></article>";
+        let converted = convert_with(html, LinkStyle::Inline);
+        let md = &converted.markdown;
+        assert!(!md.contains('\u{00A0}'), "{md:?}");
+        assert!(md.starts_with("Fish & Chips\n"), "{md:?}");
+        assert!(
+            md.contains("Open daily & late\u{2026} Tom\u{2019}s&bogus;"),
+            "{md:?}"
+        );
+        assert!(md.contains("|a b|"), "{md:?}");
+        // Code is shown as written
+        assert!(md.contains("x = \"&nbsp;\""), "{md:?}");
+
+        // A literal non-breaking space converts and hashes the same
+        let literal = convert_with(&html.replacen("&nbsp;", "\u{00A0}", 1), LinkStyle::Inline);
+        assert_eq!(literal.markdown, converted.markdown);
+        assert_eq!(literal.hash, converted.hash);
+    }
+
+    #[test]
+    fn prose_word_count_skips_code_and_links() {
+        let md = "# Setup guide\n\nRun the [installer](https://example.com/install.sh) first.\n\n```sh\ncurl -sSf https://example.com | sh\nexport PATH=$HOME/bin:$PATH\n```\n\n| Flag | Meaning |\n|------|---------|\n\n1. Done";
+        // Setup guide / Run the installer first. / Flag Meaning / Done
+        assert_eq!(count_prose_words(md), 9);
+    }
+
+    #[test]
+    fn relative_image_urls_are_resolved() {
+        let opts = ConvertOptions {
+            keep_images: true,
+            keep_tags: Vec::new(),
+            base_url: "https://example.com/blog/post".to_string(),
+            link_style: LinkStyle::Inline,
+            normalize_urls: false,
+        };
+        let html = r#"<p><img src="img/chart.png" alt="Chart"></p><p><img srcset="/media/photo-640.jpg 640w, /media/photo-1280.jpg 1280w" alt="Photo"></p>"#;
+        let markdown = convert_to_markdown(html, &opts).unwrap().markdown;
+        assert!(markdown.contains("![Chart](https://example.com/blog/img/chart.png)"));
+        assert!(markdown.contains("![Photo](https://example.com/media/photo-640.jpg)"));
+    }
+
+    #[test]
+    fn lazy_load_image_prefers_data_src() {
+        let html = r#"<img src="data:image/gif;base64,R0lGOD" data-src="/real.png" alt="x">"#;
+        assert_eq!(
+            resolve_image_sources(html, "https://example.com/a/"),
+            r#"<img src="https://example.com/real.png" alt="x">"#
+        );
+    }
+
+    #[test]
+    fn nested_lists_keep_structure() {
+        let html = r#"
+<ul>
+  <li>One
+    <ul>
+      <li>Two
+        <ol>
+          <li>Three</li>
+          <li>Four</li>
+        </ol>
+      </li>
+    </ul>
+  </li>
+  <li>Five</li>
+</ul>
+<ol><li>a</li><li>b<ol><li>c</li><li>d</li></ol></li><li>e</li></ol>
+"#;
+        assert_eq!(
+            convert(html),
+            "* One\n  * Two\n    1. Three\n    2. Four\n* Five\n\n1. a\n2. b\n  1. c\n  2. d\n3. e"
+        );
+    }
+
+    #[test]
+    fn blockquote_paragraphs_are_prefixed() {
+        let html =
+            "<blockquote><p>First paragraph.</p><p>Second paragraph.</p></blockquote><p>After.</p>";
+        assert_eq!(
+            convert(html),
+            "> First paragraph.\n>\n> Second paragraph.\n\nAfter."
+        );
+    }
+
+    #[test]
+    fn reference_links_are_numbered_at_the_end() {
+        let html = r#"<p>See <a href="https://example.com/docs">the docs</a> and <a href="/faq">the FAQ</a>.</p>"#;
+        let inline = convert_with(html, LinkStyle::Inline);
+        assert_eq!(
+            inline.markdown,
+            "See [the docs](https://example.com/docs) and [the FAQ](https://example.com/faq)."
+        );
+
+        let reference = convert_with(html, LinkStyle::Reference);
+        assert_eq!(
+            reference.markdown,
+            "See [the docs][1] and [the FAQ][2].\n\n[1]: https://example.com/docs\n[2]: https://example.com/faq"
+        );
+        assert_eq!(reference.links, inline.links);
+    }
+
+    #[test]
+    fn normalized_links_drop_tracking_params_and_fragments() {
+        let html = r#"<p>
+            <a href="https://Example.COM:443/post?utm_source=feed&amp;id=7#comments">one</a>
+            <a href="/post?id=7&amp;utm_source=newsletter&amp;fbclid=abc">two</a>
+            <a href="https://example.com/other?gclid=x&amp;UTM_Medium=y">three</a>
+        </p>"#;
+        let links = |normalize_urls| {
+            let opts = ConvertOptions {
+                keep_images: false,
+                keep_tags: Vec::new(),
+                base_url: "https://example.com/".to_string(),
+                link_style: LinkStyle::Inline,
+                normalize_urls,
+            };
+            convert_to_markdown(html, &opts).unwrap().links
+        };
+
+        assert_eq!(links(false).len(), 3);
+        assert_eq!(
+            links(true),
+            ["https://example.com/other", "https://example.com/post?id=7"]
+        );
+        assert_eq!(normalize_url("not a url"), "not a url");
+    }
+
+    #[test]
+    fn normalizing_leaves_other_query_parameters_as_written() {
+        assert_eq!(
+            normalize_url("https://example.com/s?flag&q=a%20b+c"),
+            "https://example.com/s?flag&q=a%20b+c"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/s?flag&utm_source=x&q=caf%C3%A9"),
+            "https://example.com/s?flag&q=caf%C3%A9"
+        );
+        assert_eq!(
+            normalize_url("https://example.com/s?utm%5Fsource=x"),
+            "https://example.com/s"
+        );
+    }
+}
@@ -1,15 +1,31 @@
 use anyhow::Result;
+use regex::Regex;
 /// HTML content extraction (Readability-lite heuristic).
 use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Pages at or below this many words are checked for not-found/login phrasing.
+/// Longer pages are assumed to be real content that merely mentions them.
+const SOFT_404_MAX_WORDS: u32 = 50;
 
 pub struct ExtractOptions {
     pub selector: Option<String>,
+    /// Keep every element matching `selector`, in document order, instead of
+    /// only the one with the most text.
+    pub selector_all: bool,
+    /// Selectors for subtrees to drop from the chosen content.
+    pub exclude: Vec<String>,
 }
 
 pub struct ExtractedContent {
     pub title: String,
     pub byline: Option<String>,
+    pub description: Option<String>,
     pub canonical_url: Option<String>,
+    /// Absolute target of a client-side redirect: a `<meta http-equiv="refresh">`
+    /// with a URL, or an inline script that only assigns `window.location`.
+    pub refresh_url: Option<String>,
     pub main_html: String,
 }
 
@@ -23,23 +39,55 @@ pub fn extract_content(
     // Extract metadata
     let title = extract_title(&document);
     let byline = extract_byline(&document);
+    let description = extract_description(&document);
     let canonical_url = extract_canonical(&document, base_url);
+    let refresh_url = extract_refresh(&document, base_url);
 
     // Extract main content
     let main_html = if let Some(ref selector_str) = opts.selector {
-        extract_by_selector(&document, selector_str)?
+        extract_by_selector(&document, selector_str, opts.selector_all)?
     } else {
         extract_by_heuristic(&document)?
     };
+    let main_html = remove_excluded(&main_html, &opts.exclude)?;
 
     Ok(ExtractedContent {
         title,
         byline,
+        description,
         canonical_url,
+        refresh_url,
         main_html,
     })
 }
 
+/// Explain why an extracted page looks like a soft 404 or login wall, if it does.
+///
+/// A page is flagged when it has fewer than `min_words` words, or when it is
+/// short and its title or text reads like an error or sign-in page.
+pub fn soft_404_reason(title: &str, text: &str, word_count: u32, min_words: u32) -> Option<String> {
+    if word_count < min_words {
+        return Some(format!(
+            "only {} words extracted (minimum {})",
+            word_count, min_words
+        ));
+    }
+    if word_count > SOFT_404_MAX_WORDS {
+        return None;
+    }
+
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN
+        .get_or_init(|| Regex::new(r"(?i)\b(not found|sign in|log in|403 forbidden)\b").unwrap());
+    [("title", title), ("text", text)]
+        .into_iter()
+        .find_map(|(field, value)| {
+            pattern
+                .find(value)
+                .map(|m| format!("{} mentions \"{}\"", field, m.as_str()))
+        })
+}
+
 fn extract_title(document: &Html) -> String {
     // Try <title> tag first
     if let Ok(selector) = Selector::parse("title") {
@@ -65,53 +113,174 @@ fn extract_title(document: &Html) -> String {
     String::new()
 }
 
+/// Class-based byline matches longer than this are page furniture, not a name.
+const MAX_CLASS_BYLINE_CHARS: usize = 120;
+
 fn extract_byline(document: &Html) -> Option<String> {
-    // Try meta author
-    if let Ok(selector) = Selector::parse(r#"meta[name="author"]"#) {
-        if let Some(meta_el) = document.select(&selector).next() {
-            if let Some(content) = meta_el.value().attr("content") {
-                if !content.trim().is_empty() {
-                    return Some(content.trim().to_string());
-                }
-            }
-        }
+    // Explicit metadata first, then the class-name guess
+    let meta = |selector: &str| {
+        let selector = Selector::parse(selector).ok()?;
+        document
+            .select(&selector)
+            .filter_map(|el| el.value().attr("content"))
+            .find(|content| !content.trim().is_empty() && !content.starts_with("http"))
+            .map(str::to_string)
+    };
+    let element_text = |selector: &str| {
+        let selector = Selector::parse(selector).ok()?;
+        document
+            .select(&selector)
+            .map(|el| el.text().collect::<String>())
+            .find(|text| !text.trim().is_empty())
+    };
+
+    meta(r#"meta[name="author"]"#)
+        .or_else(|| meta(r#"meta[property="article:author"]"#))
+        .or_else(|| element_text(r#"a[rel~="author"]"#))
+        .or_else(|| extract_jsonld_author(document))
+        .map(|name| clean_byline(&name))
+        .or_else(|| {
+            element_text(r#"[class*="author"], [class*="byline"]"#)
+                .map(|text| clean_byline(&text))
+                .filter(|text| text.chars().count() <= MAX_CLASS_BYLINE_CHARS)
+        })
+        .filter(|name| !name.is_empty())
+}
+
+/// First `author` name found in the page's JSON-LD blocks.
+fn extract_jsonld_author(document: &Html) -> Option<String> {
+    let selector = Selector::parse(r#"script[type="application/ld+json"]"#).ok()?;
+    document
+        .select(&selector)
+        .filter_map(|el| serde_json::from_str(&el.text().collect::<String>()).ok())
+        .find_map(|value| jsonld_author(&value))
+}
+
+fn jsonld_author(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Array(items) => items.iter().find_map(jsonld_author),
+        serde_json::Value::Object(map) => match map.get("author") {
+            Some(author) => jsonld_name(author),
+            None => map.get("@graph").and_then(jsonld_author),
+        },
+        _ => None,
     }
+}
 
-    // Try class="author" or similar
-    if let Ok(selector) = Selector::parse(r#"[class*="author"], [class*="byline"]"#) {
-        if let Some(author_el) = document.select(&selector).next() {
-            let text = author_el.text().collect::<String>();
-            if !text.trim().is_empty() {
-                return Some(text.trim().to_string());
-            }
-        }
+fn jsonld_name(author: &serde_json::Value) -> Option<String> {
+    match author {
+        serde_json::Value::String(name) => Some(name.clone()),
+        serde_json::Value::Array(items) => items.iter().find_map(jsonld_name),
+        serde_json::Value::Object(map) => map.get("name").and_then(jsonld_name),
+        _ => None,
+    }
+}
+
+/// Collapse whitespace and drop a leading "By".
+fn clean_byline(raw: &str) -> String {
+    let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    match collapsed.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("by ") => collapsed[3..].to_string(),
+        _ => collapsed,
     }
+}
 
-    None
+fn extract_description(document: &Html) -> Option<String> {
+    [
+        r#"meta[name="description"]"#,
+        r#"meta[property="og:description"]"#,
+    ]
+    .iter()
+    .filter_map(|s| Selector::parse(s).ok())
+    .find_map(|selector| {
+        document
+            .select(&selector)
+            .filter_map(|el| el.value().attr("content"))
+            .map(str::trim)
+            .find(|content| !content.is_empty())
+            .map(str::to_string)
+    })
 }
 
+/// The page's canonical URL, made absolute. Only `http(s)` targets count, so
+/// a page cannot point `--follow-canonical` at a local file.
 fn extract_canonical(document: &Html, base_url: &str) -> Option<String> {
-    if let Ok(selector) = Selector::parse(r#"link[rel="canonical"]"#) {
-        if let Some(link_el) = document.select(&selector).next() {
-            if let Some(href) = link_el.value().attr("href") {
-                // Make absolute
-                if href.starts_with("http") {
-                    return Some(href.to_string());
-                } else if let Ok(base) = url::Url::parse(base_url) {
-                    if let Ok(resolved) = base.join(href) {
-                        return Some(resolved.to_string());
-                    }
-                }
-            }
-        }
-    }
-    None
+    let selector = Selector::parse(r#"link[rel="canonical"]"#).ok()?;
+    let href = document.select(&selector).next()?.value().attr("href")?;
+    let resolved = match url::Url::parse(href) {
+        Ok(absolute) => absolute,
+        Err(_) => url::Url::parse(base_url).ok()?.join(href.trim()).ok()?,
+    };
+    matches!(resolved.scheme(), "http" | "https").then(|| resolved.to_string())
+}
+
+fn extract_refresh(document: &Html, base_url: &str) -> Option<String> {
+    let meta = Selector::parse("meta[http-equiv][content]").ok()?;
+    let from_meta = document
+        .select(&meta)
+        .filter(|el| {
+            el.value()
+                .attr("http-equiv")
+                .is_some_and(|v| v.trim().eq_ignore_ascii_case("refresh"))
+        })
+        .find_map(|el| refresh_content_url(el.value().attr("content")?));
+
+    // Only a script that does nothing but navigate; anything smarter needs a
+    // browser
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let location = LOCATION.get_or_init(|| {
+        Regex::new(
+            r#"^\s*(?:(?:window|document|self|top)\.)?location(?:\.href)?(?:\s*=\s*|\.(?:replace|assign)\(\s*)["']([^"']+)["']\s*\)?\s*;?\s*$"#,
+        )
+        .unwrap()
+    });
+    let script = Selector::parse("script:not([src])").ok()?;
+    let from_script = || {
+        document.select(&script).find_map(|el| {
+            let code = el.text().collect::<String>();
+            Some(location.captures(&code)?[1].to_string())
+        })
+    };
+
+    let target = from_meta.or_else(from_script)?;
+    let resolved = url::Url::parse(base_url).ok()?.join(target.trim()).ok()?;
+    matches!(resolved.scheme(), "http" | "https").then(|| resolved.to_string())
+}
+
+/// The URL of a refresh `content` value such as `0; url=/next` or
+/// `5;URL='/next'`. A bare delay (a timed reload of the same page) has none.
+fn refresh_content_url(content: &str) -> Option<String> {
+    let (_delay, rest) = content.split_once([';', ','])?;
+    let rest = rest.trim_start();
+    let rest = match rest.get(..3) {
+        Some(key) if key.eq_ignore_ascii_case("url") => rest[3..].trim_start().strip_prefix('=')?,
+        _ => rest,
+    };
+    let target = rest.trim().trim_matches(|c| c == '\'' || c == '"').trim();
+    (!target.is_empty()).then(|| target.to_string())
 }
 
-fn extract_by_selector(document: &Html, selector_str: &str) -> Result<String> {
+/// The HTML of the matching element with the most text or, with `all`, of
+/// every match in document order. Under `all`, matches nested inside another
+/// match are left to their ancestor so no text appears twice.
+fn extract_by_selector(document: &Html, selector_str: &str, all: bool) -> Result<String> {
     let selector =
         Selector::parse(selector_str).map_err(|e| anyhow::anyhow!("Invalid selector: {:?}", e))?;
 
+    if all {
+        let matches: Vec<_> = document.select(&selector).collect();
+        let ids: HashSet<_> = matches.iter().map(|el| el.id()).collect();
+        let html: String = matches
+            .iter()
+            .filter(|el| !el.ancestors().any(|a| ids.contains(&a.id())))
+            .map(|el| el.html())
+            .collect();
+        if html.is_empty() {
+            anyhow::bail!("No matching elements for selector: {}", selector_str)
+        }
+        return Ok(html);
+    }
+
     // Find the first matching element with the most text
     let mut best_el = None;
     let mut best_len = 0;
@@ -132,6 +301,26 @@ fn extract_by_selector(document: &Html, selector_str: &str) -> Result<String> {
     }
 }
 
+/// Remove every element matching one of `selectors` from `html`.
+fn remove_excluded(html: &str, selectors: &[String]) -> Result<String> {
+    if selectors.is_empty() {
+        return Ok(html.to_string());
+    }
+
+    let mut fragment = Html::parse_fragment(html);
+    for selector_str in selectors {
+        let selector = Selector::parse(selector_str)
+            .map_err(|e| anyhow::anyhow!("Invalid exclude selector: {:?}", e))?;
+        let ids: Vec<_> = fragment.select(&selector).map(|el| el.id()).collect();
+        for id in ids {
+            if let Some(mut node) = fragment.tree.get_mut(id) {
+                node.detach();
+            }
+        }
+    }
+    Ok(fragment.root_element().inner_html())
+}
+
 fn extract_by_heuristic(document: &Html) -> Result<String> {
     // Simple heuristic: look for common content containers
     let candidate_selectors = vec![
@@ -165,3 +354,153 @@ fn extract_by_heuristic(document: &Html) -> Result<String> {
 
     anyhow::bail!("Could not extract main content")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_targets_are_resolved() {
+        let base = "https://example.com/docs/old";
+        let refresh = |head: &str| {
+            let html = format!("<html><head>{head}</head><body></body></html>");
+            extract_refresh(&Html::parse_document(&html), base)
+        };
+        for content in ["0;url=new", "0; URL='new'", "5 , url = new", "0;new"] {
+            let meta = format!(r#"<meta http-equiv="refresh" content="{content}">"#);
+            assert_eq!(
+                refresh(&meta).as_deref(),
+                Some("https://example.com/docs/new"),
+                "{content}"
+            );
+        }
+        assert_eq!(refresh(r#"<meta http-equiv="refresh" content="30">"#), None);
+        assert_eq!(
+            refresh(r#"<meta http-equiv="refresh" content="0;url=javascript:void(0)">"#),
+            None
+        );
+        assert_eq!(
+            refresh("<script>location.replace('/home')</script>").as_deref(),
+            Some("https://example.com/home")
+        );
+        assert_eq!(
+            refresh("<script>if (x) { window.location = '/home'; }</script>"),
+            None
+        );
+    }
+
+    #[test]
+    fn login_wall_is_soft_404() {
+        let reason = soft_404_reason("Example", "Please sign in to continue.", 5, 0);
+        assert_eq!(reason.as_deref(), Some("text mentions \"sign in\""));
+
+        let reason = soft_404_reason("Page Not Found", "Sorry.", 1, 0);
+        assert_eq!(reason.as_deref(), Some("title mentions \"Not Found\""));
+    }
+
+    #[test]
+    fn real_article_is_not_soft_404() {
+        let text = "Log in to the dashboard, then open settings. ".repeat(10);
+        let words = text.split_whitespace().count() as u32;
+        assert!(words > SOFT_404_MAX_WORDS);
+        assert_eq!(
+            soft_404_reason("Configuring alerts", &text, words, 20),
+            None
+        );
+    }
+
+    fn byline(html: &str) -> Option<String> {
+        extract_byline(&Html::parse_document(html))
+    }
+
+    #[test]
+    fn byline_from_rel_author() {
+        let html = r#"<div class="author-sidebar">Lots of unrelated text</div>
+            <p>Posted <a rel="author" href="/u/ada">By
+                Ada   Lovelace</a></p>"#;
+        assert_eq!(byline(html).as_deref(), Some("Ada Lovelace"));
+    }
+
+    #[test]
+    fn byline_from_jsonld() {
+        let html = r#"<script type="application/ld+json">
+            {"@context": "https://schema.org", "@graph": [
+              {"@type": "WebSite", "name": "Example"},
+              {"@type": "Article", "author": [{"@type": "Person", "name": "Grace Hopper"}]}
+            ]}
+            </script>"#;
+        assert_eq!(byline(html).as_deref(), Some("Grace Hopper"));
+    }
+
+    #[test]
+    fn long_class_byline_is_ignored() {
+        let blob = "About the authors ".repeat(10);
+        let html = format!(r#"<div class="authors">{}</div>"#, blob);
+        assert_eq!(byline(&html), None);
+        assert_eq!(
+            byline(r#"<span class="byline">by Jane Doe</span>"#).as_deref(),
+            Some("Jane Doe")
+        );
+    }
+
+    #[test]
+    fn description_falls_back_to_og() {
+        let document = Html::parse_document(
+            r#"<head><meta property="og:description" content="  A short summary. "></head>"#,
+        );
+        assert_eq!(
+            extract_description(&document).as_deref(),
+            Some("A short summary.")
+        );
+
+        let document = Html::parse_document(
+            r#"<head><meta name="description" content="Plain"><meta property="og:description" content="OG"></head>"#,
+        );
+        assert_eq!(extract_description(&document).as_deref(), Some("Plain"));
+        assert_eq!(extract_description(&Html::parse_document("<p>x</p>")), None);
+    }
+
+    #[test]
+    fn excluded_subtrees_are_removed() {
+        let html = r#"<article><p>Keep this.</p><div class="promo"><p>Buy now!</p></div><aside class="related">More</aside></article>"#;
+        let opts = ExtractOptions {
+            selector: Some("article".to_string()),
+            selector_all: false,
+            exclude: vec![".promo".to_string(), ".related".to_string()],
+        };
+        let extracted = extract_content(html, "https://example.com/", &opts).unwrap();
+        assert!(extracted.main_html.contains("Keep this."));
+        assert!(!extracted.main_html.contains("Buy now!"));
+        assert!(!extracted.main_html.contains("More"));
+    }
+
+    #[test]
+    fn selector_all_keeps_every_block_in_order() {
+        let html = r#"<body><div class="post-body"><p>Part one.</p></div><aside>Ads</aside><div class="post-body"><p>Part two, the longest of the three.</p></div><div class="post-body"><p>Part three.</p><div class="post-body">Nested.</div></div></body>"#;
+        let mut opts = ExtractOptions {
+            selector: Some(".post-body".to_string()),
+            selector_all: false,
+            exclude: Vec::new(),
+        };
+        let best = extract_content(html, "https://example.com/", &opts).unwrap();
+        assert!(best.main_html.contains("Part two"));
+        assert!(!best.main_html.contains("Part one."));
+
+        opts.selector_all = true;
+        let all = extract_content(html, "https://example.com/", &opts)
+            .unwrap()
+            .main_html;
+        let one = all.find("Part one.").unwrap();
+        let two = all.find("Part two").unwrap();
+        let three = all.find("Part three.").unwrap();
+        assert!(one < two && two < three, "{all}");
+        assert_eq!(all.matches("Nested.").count(), 1);
+        assert!(!all.contains("Ads"));
+    }
+
+    #[test]
+    fn min_words_threshold() {
+        assert!(soft_404_reason("Post", "Hello there world.", 3, 5).is_some());
+        assert!(soft_404_reason("Post", "Hello there world.", 3, 0).is_none());
+    }
+}
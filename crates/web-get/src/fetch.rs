@@ -1,13 +1,62 @@
-/// HTTP fetching with size and timeout limits.
-use anyhow::Result;
+/// HTTP and local-file fetching with size and timeout limits.
+use crate::hosts::{self, HostFilter};
+pub use agent_tools_common::{parse_duration, parse_size};
+use anyhow::{Context, Result};
 use bytes::Bytes;
 use reqwest::Client;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct FetchOptions {
     pub timeout: Duration,
     pub max_bytes: usize,
-    pub user_agent: String,
+    pub user_agents: UserAgents,
+    /// Redirects to follow before giving up; 0 returns the 3xx response as-is.
+    pub max_redirects: usize,
+    /// Sent as `Accept-Language` when set.
+    pub accept_language: Option<String>,
+    /// Credentials sent as `Authorization` to the requested URL's origin.
+    pub auth: Option<Auth>,
+    /// Hosts that may be requested, checked again on every redirect hop.
+    pub hosts: HostFilter,
+    /// Permit hosts that are or resolve to private, loopback, or link-local
+    /// addresses, which are otherwise refused on every hop.
+    pub allow_private: bool,
+    /// Requests go through a proxy, which resolves host names itself, so
+    /// names are checked against private addresses before each request.
+    pub proxied: bool,
+}
+
+/// HTTP credentials. `Debug` leaves the secrets out so they never reach logs.
+#[derive(Clone, PartialEq)]
+pub enum Auth {
+    Basic { user: String, password: String },
+    Bearer(String),
+}
+
+impl Auth {
+    /// Parse `--basic-auth user:pass`; the password may be empty but the colon
+    /// is required.
+    pub fn basic(credentials: &str) -> Result<Auth> {
+        let (user, password) = credentials
+            .split_once(':')
+            .context("invalid --basic-auth: expected user:pass")?;
+        Ok(Auth::Basic {
+            user: user.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Basic { user, .. } => write!(f, "Basic({user}:…)"),
+            Auth::Bearer(_) => write!(f, "Bearer(…)"),
+        }
+    }
 }
 
 impl Default for FetchOptions {
@@ -15,27 +64,213 @@ impl Default for FetchOptions {
         Self {
             timeout: Duration::from_secs(15),
             max_bytes: 10 * 1024 * 1024, // 10MB
-            user_agent: "web-get/0.1".to_string(),
+            user_agents: UserAgents::default(),
+            max_redirects: 10,
+            accept_language: None,
+            auth: None,
+            hosts: HostFilter::default(),
+            allow_private: false,
+            proxied: false,
         }
     }
 }
 
+/// The `User-Agent` values to send, handed out round-robin: each fetch takes
+/// the next one and keeps it across its redirects. Clones share the rotation.
+#[derive(Debug, Clone)]
+pub struct UserAgents {
+    agents: Arc<[String]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl UserAgents {
+    /// Rotate through `agents`, which must not be empty.
+    pub fn new(agents: Vec<String>) -> Result<UserAgents> {
+        anyhow::ensure!(!agents.is_empty(), "no user agents given");
+        Ok(UserAgents {
+            agents: agents.into(),
+            next: Arc::default(),
+        })
+    }
+
+    /// Read `--user-agent-file`: one user agent per line, skipping blank
+    /// lines and `#` comments.
+    pub fn from_file(path: &Path) -> Result<UserAgents> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let agents = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        UserAgents::new(agents).with_context(|| format!("{} lists no user agents", path.display()))
+    }
+
+    /// The user agent for the next fetch.
+    pub fn next(&self) -> &str {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.agents[i % self.agents.len()]
+    }
+}
+
+impl Default for UserAgents {
+    fn default() -> Self {
+        UserAgents::new(vec!["web-get/0.1".to_string()]).expect("one user agent")
+    }
+}
+
 pub struct FetchResult {
+    #[allow(dead_code)]
     pub url: String,
     pub final_url: String,
     pub content_type: String,
     pub body: Bytes,
     pub truncated: bool,
+    /// URLs that answered with a redirect, in the order they were visited.
+    pub redirects: Vec<String>,
+    /// The response's `Content-Language` header, if any.
+    pub content_language: Option<String>,
+}
+
+/// The whole fetch, including every redirect hop and the body read, took
+/// longer than `FetchOptions::timeout`.
+#[derive(Debug)]
+pub struct Timeout(pub Duration);
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Fetch an `http(s)` URL over the network, or read a `file://` URL or plain
+/// local path from disk, failing with [`Timeout`] once `opts.timeout` passes.
+pub async fn fetch(client: &Client, url: &str, opts: &FetchOptions) -> Result<FetchResult> {
+    // reqwest's per-request timeout does not reliably bound a body that
+    // trickles in, so the deadline is enforced around the whole operation.
+    tokio::time::timeout(opts.timeout, fetch_any(client, url, opts))
+        .await
+        .map_err(|_| Timeout(opts.timeout))?
+}
+
+async fn fetch_any(client: &Client, url: &str, opts: &FetchOptions) -> Result<FetchResult> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+            fetch_url(client, url, opts).await
+        }
+        Ok(parsed) if parsed.scheme() == "file" => {
+            opts.hosts.check_file(url)?;
+            let path = parsed
+                .to_file_path()
+                .map_err(|_| anyhow::anyhow!("not a local file URL: {}", url))?;
+            fetch_file(url, &path, opts).await
+        }
+        // A single-letter scheme is a Windows drive letter, not a URL
+        Ok(parsed) if parsed.scheme().len() > 1 => {
+            anyhow::bail!("unsupported URL scheme: {}", parsed.scheme())
+        }
+        _ => {
+            opts.hosts.check_file(url)?;
+            fetch_file(url, Path::new(url), opts).await
+        }
+    }
+}
+
+/// Read a local file, guessing its content type from the extension.
+async fn fetch_file(url: &str, path: &Path, opts: &FetchOptions) -> Result<FetchResult> {
+    let path = tokio::fs::canonicalize(path)
+        .await
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    let mut body = Bytes::from(tokio::fs::read(&path).await?);
+    let truncated = body.len() > opts.max_bytes;
+    if truncated {
+        body.truncate(opts.max_bytes);
+    }
+
+    let final_url = url::Url::from_file_path(&path)
+        .map_err(|_| anyhow::anyhow!("cannot form a file URL for {}", path.display()))?
+        .to_string();
+
+    Ok(FetchResult {
+        url: url.to_string(),
+        final_url,
+        content_type: guess_content_type(&path).to_string(),
+        body,
+        truncated,
+        redirects: Vec::new(),
+        content_language: None,
+    })
 }
 
-pub async fn fetch_url(client: &Client, url: &str, opts: &FetchOptions) -> Result<FetchResult> {
-    let resp = client
-        .get(url)
-        .header(reqwest::header::USER_AGENT, &opts.user_agent)
-        .timeout(opts.timeout)
-        .send()
-        .await?
-        .error_for_status()?;
+fn guess_content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("html" | "htm" | "xhtml") => "text/html",
+        Some("pdf") => "application/pdf",
+        Some("txt" | "md") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Fetch `url`, following redirects by hand so each hop can be recorded.
+/// The client must be built with `redirect::Policy::none()`.
+async fn fetch_url(client: &Client, url: &str, opts: &FetchOptions) -> Result<FetchResult> {
+    let mut current = reqwest::Url::parse(url)?;
+    let origin = current.origin();
+    let mut redirects = Vec::new();
+    let user_agent = opts.user_agents.next();
+    tracing::info!("user-agent: {user_agent}");
+
+    let resp = loop {
+        opts.hosts.check(current.host_str().unwrap_or_default())?;
+        if !opts.allow_private {
+            hosts::check_public(&current, opts.proxied).await?;
+        }
+        let mut request = client
+            .get(current.clone())
+            .header(reqwest::header::USER_AGENT, user_agent)
+            .timeout(opts.timeout);
+        if let Some(accept_language) = &opts.accept_language {
+            request = request.header(reqwest::header::ACCEPT_LANGUAGE, accept_language);
+        }
+        // Like curl, credentials are not forwarded to another origin on redirect
+        match &opts.auth {
+            _ if current.origin() != origin => {}
+            Some(Auth::Basic { user, password }) => {
+                request = request.basic_auth(user, Some(password));
+            }
+            Some(Auth::Bearer(token)) => request = request.bearer_auth(token),
+            None => {}
+        }
+        let request = request.build()?;
+        tracing::info!("{} {}", request.method(), request.url());
+        tracing::debug!("request headers: {:?}", request.headers());
+        let resp = client.execute(request).await?;
+        tracing::debug!("response headers: {:?}", resp.headers());
+
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok());
+        let next = match location {
+            Some(location) if resp.status().is_redirection() && opts.max_redirects > 0 => {
+                current.join(location)?
+            }
+            _ => break resp.error_for_status()?,
+        };
+
+        if redirects.len() >= opts.max_redirects {
+            anyhow::bail!("too many redirects (limit {})", opts.max_redirects);
+        }
+        redirects.push(current.to_string());
+        current = next;
+    };
 
     let final_url = resp.url().to_string();
     let content_type = resp
@@ -44,9 +279,23 @@ pub async fn fetch_url(client: &Client, url: &str, opts: &FetchOptions) -> Resul
         .and_then(|v| v.to_str().ok())
         .unwrap_or("application/octet-stream")
         .to_string();
+    let content_language = resp
+        .headers()
+        .get(reqwest::header::CONTENT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
 
     // Read body with size limit
+    let status = resp.status();
     let body = resp.bytes().await?;
+    tracing::info!(
+        "{} {} {} {} bytes",
+        status.as_u16(),
+        final_url,
+        content_type,
+        body.len()
+    );
     let truncated = body.len() > opts.max_bytes;
     let body = if truncated {
         body.slice(..opts.max_bytes)
@@ -60,64 +309,94 @@ pub async fn fetch_url(client: &Client, url: &str, opts: &FetchOptions) -> Resul
         content_type,
         body,
         truncated,
+        redirects,
+        content_language,
     })
 }
 
-/// Parse a human-readable size string (e.g., "10MB", "1GB").
-pub fn parse_size(s: &str) -> Result<usize> {
-    let s = s.trim().to_uppercase();
-    let (num_part, unit) = if let Some(pos) = s.find(|c: char| c.is_alphabetic()) {
-        (&s[..pos], &s[pos..])
-    } else {
-        (s.as_str(), "")
-    };
-
-    let num: usize = num_part.parse()?;
-    let multiplier = match unit {
-        "B" | "" => 1,
-        "KB" => 1024,
-        "MB" => 1024 * 1024,
-        "GB" => 1024 * 1024 * 1024,
-        _ => anyhow::bail!("Unknown size unit: {}", unit),
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(num * multiplier)
-}
+    /// Options for fetching from the test server, which listens on loopback.
+    fn local() -> FetchOptions {
+        FetchOptions {
+            allow_private: true,
+            ..Default::default()
+        }
+    }
 
-/// Parse a human-readable duration string (e.g., "15s", "1m").
-pub fn parse_duration(s: &str) -> Result<Duration> {
-    let s = s.trim().to_lowercase();
-    let (num_part, unit) = if let Some(pos) = s.find(|c: char| c.is_alphabetic()) {
-        (&s[..pos], &s[pos..])
-    } else {
-        (s.as_str(), "s")
-    };
+    #[tokio::test]
+    async fn verbose_log_records_request_and_response() {
+        use crate::test_server::{Response, TestServer};
+        use std::sync::{Arc, Mutex};
 
-    let num: u64 = num_part.parse()?;
-    let duration = match unit {
-        "s" | "sec" => Duration::from_secs(num),
-        "m" | "min" => Duration::from_secs(num * 60),
-        "h" | "hour" => Duration::from_secs(num * 3600),
-        _ => anyhow::bail!("Unknown duration unit: {}", unit),
-    };
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
 
-    Ok(duration)
-}
+        let server = TestServer::start(|_| Response::html("<p>hello</p>")).await;
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let captured = captured.clone();
+                move || captured.clone()
+            })
+            .with_ansi(false)
+            .with_max_level(tracing::Level::INFO)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let url = server.url("/page");
+        fetch(&Client::new(), &url, &local()).await.expect("fetch");
 
-    #[test]
-    fn test_parse_size() {
-        assert_eq!(parse_size("1024").unwrap(), 1024);
-        assert_eq!(parse_size("1KB").unwrap(), 1024);
-        assert_eq!(parse_size("10MB").unwrap(), 10 * 1024 * 1024);
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains(&format!("GET {url}")), "{log}");
+        assert!(log.contains("user-agent: web-get/0.1"), "{log}");
+        assert!(
+            log.contains(&format!("200 {url} text/html; charset=utf-8 12 bytes")),
+            "{log}"
+        );
+        assert!(!log.contains("headers"), "{log}");
     }
 
-    #[test]
-    fn test_parse_duration() {
-        assert_eq!(parse_duration("15s").unwrap(), Duration::from_secs(15));
-        assert_eq!(parse_duration("1m").unwrap(), Duration::from_secs(60));
+    #[tokio::test]
+    async fn basic_auth_is_sent_and_kept_out_of_logs() {
+        use crate::test_server::{Response, TestServer};
+
+        let server = TestServer::start(|req| {
+            if req.header("Authorization") == Some("Basic dXNlcjpzM2NyZXQ=") {
+                Response::html("<p>members only</p>")
+            } else {
+                Response::status(401).header("WWW-Authenticate", "Basic realm=\"site\"")
+            }
+        })
+        .await;
+        let url = server.url("/private");
+
+        let Err(err) = fetch(&Client::new(), &url, &local()).await else {
+            panic!("fetched without credentials");
+        };
+        assert!(format!("{err:#}").contains("401"), "{err:#}");
+
+        let opts = FetchOptions {
+            auth: Some(Auth::basic("user:s3cret").unwrap()),
+            ..local()
+        };
+        let result = fetch(&Client::new(), &url, &opts)
+            .await
+            .expect("authorized");
+        assert_eq!(&result.body[..], b"<p>members only</p>");
+
+        assert!(!format!("{:?}", opts.auth).contains("s3cret"));
+        assert!(!format!("{:?}", Auth::Bearer("tok3n".into())).contains("tok3n"));
+        assert!(Auth::basic("no-colon").is_err());
     }
 }
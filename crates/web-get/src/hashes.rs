@@ -0,0 +1,68 @@
+/// Content hashes from earlier runs, kept in `--cache-dir` so `--skip-unchanged`
+/// can tell which pages changed without relying on ETags.
+use agent_tools_common::AtomicFile;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "hashes.json";
+
+/// URL → `hash` of the last document seen for it, stored as one JSON object.
+#[derive(Debug, Default)]
+pub struct HashStore {
+    path: PathBuf,
+    hashes: BTreeMap<String, String>,
+}
+
+impl HashStore {
+    /// Open the store in `dir`, creating the directory if needed. A missing
+    /// file is an empty store.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("cannot create cache dir {}", dir.display()))?;
+        let path = dir.join(FILE_NAME);
+        let hashes = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("corrupt hash store {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+        };
+        Ok(Self { path, hashes })
+    }
+
+    /// Record `hash` for `url`, returning whether it matches the stored one.
+    pub fn update(&mut self, url: &str, hash: &str) -> bool {
+        match self.hashes.insert(url.to_string(), hash.to_string()) {
+            Some(previous) => previous == hash,
+            None => false,
+        }
+    }
+
+    /// Write the store back, replacing the file only once it is complete.
+    pub fn save(&self) -> Result<()> {
+        let mut file = AtomicFile::create(&self.path)?;
+        serde_json::to_writer_pretty(&mut file, &self.hashes)?;
+        file.write_all(b"\n")?;
+        file.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_survive_a_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let mut store = HashStore::open(&cache).unwrap();
+        assert!(!store.update("https://example.com/a", "h1"));
+        assert!(store.update("https://example.com/a", "h1"));
+        store.save().unwrap();
+
+        let mut store = HashStore::open(&cache).unwrap();
+        assert!(store.update("https://example.com/a", "h1"));
+        assert!(!store.update("https://example.com/a", "h2"));
+    }
+}
@@ -0,0 +1,421 @@
+/// Host allow/deny lists and the private-address guard, checked before every
+/// request and redirect hop.
+use anyhow::Result;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// The hosts `--allow-host` and `--deny-host` admit. Patterns are exact host
+/// names or `*.example.com`, which matches any subdomain of `example.com`
+/// but not `example.com` itself. A denied host is blocked even if allowed;
+/// with an allowlist, every host not on it is blocked too. While either list
+/// is set, local files are blocked unless `allow_files` (`--allow-files`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostFilter {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub allow_files: bool,
+}
+
+/// A request to a host the [`HostFilter`] does not admit.
+#[derive(Debug)]
+pub struct HostBlocked {
+    pub host: String,
+    pub denied: bool,
+}
+
+impl std::fmt::Display for HostBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.denied {
+            write!(f, "host {} matches --deny-host", self.host)
+        } else {
+            write!(f, "host {} is not in --allow-host", self.host)
+        }
+    }
+}
+
+impl std::error::Error for HostBlocked {}
+
+/// A local file or `file://` URL read while host lists are in force.
+#[derive(Debug)]
+pub struct FileBlocked {
+    pub path: String,
+}
+
+impl std::fmt::Display for FileBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "local file {} is blocked while --allow-host or --deny-host is set; pass --allow-files to read it",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for FileBlocked {}
+
+impl HostFilter {
+    /// Fail with [`HostBlocked`] unless `host` may be fetched.
+    pub fn check(&self, host: &str) -> Result<(), HostBlocked> {
+        let host = normalize(host);
+        let matches = |patterns: &[String]| patterns.iter().any(|p| host_matches(p, &host));
+        if matches(&self.deny) {
+            return Err(HostBlocked { host, denied: true });
+        }
+        if !self.allow.is_empty() && !matches(&self.allow) {
+            return Err(HostBlocked {
+                host,
+                denied: false,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fail with [`FileBlocked`] if `path` may not be read: host lists are
+    /// meant to confine untrusted URL lists, which must not reach local files
+    /// through them either.
+    pub fn check_file(&self, path: &str) -> Result<(), FileBlocked> {
+        let restricted = !self.allow.is_empty() || !self.deny.is_empty();
+        if restricted && !self.allow_files {
+            return Err(FileBlocked {
+                path: path.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A request to a host that is, or resolves to, a private, loopback, or
+/// link-local address.
+#[derive(Debug)]
+pub struct SsrfBlocked {
+    pub host: String,
+    pub addr: IpAddr,
+}
+
+impl std::fmt::Display for SsrfBlocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.host == self.addr.to_string() {
+            write!(f, "refusing to fetch private address {}", self.addr)
+        } else {
+            write!(
+                f,
+                "refusing to fetch {}: it resolves to private address {}",
+                self.host, self.addr
+            )
+        }
+    }
+}
+
+impl std::error::Error for SsrfBlocked {}
+
+/// Fail with [`SsrfBlocked`] if `url`'s host is a private address. Host names
+/// are left to [`PublicResolver`] unless the request goes through a proxy,
+/// which resolves them itself; then they fail if any address they resolve to
+/// here is private, and a name that does not resolve here is let through.
+pub async fn check_public(url: &url::Url, proxied: bool) -> Result<(), SsrfBlocked> {
+    let blocked = |addr: IpAddr| SsrfBlocked {
+        host: url.host_str().unwrap_or_default().to_string(),
+        addr,
+    };
+    let domain = match url.host() {
+        Some(url::Host::Ipv4(ip)) => return check_addr(ip.into()).map_err(blocked),
+        Some(url::Host::Ipv6(ip)) => return check_addr(ip.into()).map_err(blocked),
+        Some(url::Host::Domain(domain)) if proxied => domain,
+        _ => return Ok(()),
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+    let Ok(addrs) = tokio::net::lookup_host((domain, port)).await else {
+        return Ok(());
+    };
+    for addr in addrs {
+        check_addr(addr.ip()).map_err(blocked)?;
+    }
+    Ok(())
+}
+
+/// A DNS resolver for the client that drops private addresses, so the
+/// addresses checked are the very ones connected to and a name cannot
+/// resolve to a public address for a check and a private one for the
+/// request. A name with only private addresses fails with [`SsrfBlocked`].
+/// The `exempt` names, the proxies requests go through, resolve unfiltered.
+#[derive(Debug, Default)]
+pub struct PublicResolver {
+    pub exempt: Vec<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = normalize(name.as_str());
+        let exempt = self.exempt.iter().any(|e| normalize(e) == host);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if exempt {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+            let (public, private): (Vec<_>, Vec<_>) =
+                addrs.into_iter().partition(|addr| !is_private(addr.ip()));
+            match private.first() {
+                Some(addr) if public.is_empty() => Err(SsrfBlocked {
+                    host,
+                    addr: addr.ip(),
+                }
+                .into()),
+                _ => Ok(Box::new(public.into_iter()) as Addrs),
+            }
+        })
+    }
+}
+
+/// The proxies reqwest sends requests through: `explicit` (`--proxy`), or
+/// else those named by the proxy environment variables.
+pub fn proxies(explicit: Option<&str>) -> Vec<url::Url> {
+    const ENV: [&str; 6] = [
+        "ALL_PROXY",
+        "all_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+        "HTTPS_PROXY",
+        "https_proxy",
+    ];
+    let configured: Vec<String> = match explicit {
+        Some(proxy) => vec![proxy.to_string()],
+        None => ENV
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .collect(),
+    };
+    configured
+        .iter()
+        .filter(|proxy| !proxy.trim().is_empty())
+        // Like reqwest, read a bare host:port as an http:// proxy
+        .filter_map(|proxy| {
+            url::Url::parse(proxy)
+                .ok()
+                .filter(|url| url.has_host())
+                .or_else(|| url::Url::parse(&format!("http://{proxy}")).ok())
+        })
+        .collect()
+}
+
+fn check_addr(addr: IpAddr) -> Result<(), IpAddr> {
+    if is_private(addr) {
+        Err(addr)
+    } else {
+        Ok(())
+    }
+}
+
+/// Loopback, RFC 1918 / unique-local, CGNAT (100.64.0.0/10), link-local
+/// (including cloud metadata at 169.254.169.254), and unspecified addresses,
+/// which reach the local host or network. IPv6 addresses that embed an IPv4
+/// one (mapped, IPv4-compatible, or NAT64) are judged by that address.
+fn is_private(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(ip) => {
+            let cgnat = ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64;
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || cgnat
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_unspecified()
+                || embedded_ipv4(ip).is_some_and(|ip| is_private(ip.into()))
+        }
+    }
+}
+
+/// The IPv4 address in `::ffff:a.b.c.d`, `::a.b.c.d`, or `64:ff9b::a.b.c.d`.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d, e, f, ..] = ip.segments();
+    let [.., w, x, y, z] = ip.octets();
+    match [a, b, c, d, e, f] {
+        [0, 0, 0, 0, 0, 0 | 0xffff] | [0x64, 0xff9b, 0, 0, 0, 0] => Some(Ipv4Addr::new(w, x, y, z)),
+        _ => None,
+    }
+}
+
+/// Parse a `--allow-host`/`--deny-host` pattern: a host name, optionally
+/// prefixed with `*.` to match its subdomains.
+pub fn parse_host_pattern(s: &str) -> Result<String> {
+    let pattern = normalize(s.trim());
+    let name = pattern.strip_prefix("*.").unwrap_or(&pattern);
+    // Colons only appear in bracketed IPv6 literals, never as a port
+    let bad_colon = name.contains(':') && !name.starts_with('[');
+    if name.is_empty() || name.contains(['*', '/']) || bad_colon {
+        anyhow::bail!("invalid host pattern {s:?}: expected e.g. example.com or *.example.com");
+    }
+    Ok(pattern)
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// Host names compare case-insensitively and ignore a trailing root dot.
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> HostFilter {
+        let parse = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|p| parse_host_pattern(p).unwrap())
+                .collect()
+        };
+        HostFilter {
+            allow: parse(allow),
+            deny: parse(deny),
+            allow_files: false,
+        }
+    }
+
+    #[test]
+    fn wildcards_match_subdomains_only() {
+        let hosts = filter(&["*.Example.com", "docs.rs"], &[]);
+        assert!(hosts.check("api.example.com").is_ok());
+        assert!(hosts.check("a.b.EXAMPLE.com.").is_ok());
+        assert!(hosts.check("docs.rs").is_ok());
+        assert!(hosts.check("example.com").is_err());
+        assert!(hosts.check("badexample.com").is_err());
+        assert!(hosts.check("crates.docs.rs").is_err());
+        assert!(filter(&["[::1]"], &[]).check("[::1]").is_ok());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let hosts = filter(&["*.example.com"], &["admin.example.com"]);
+        assert!(hosts.check("www.example.com").is_ok());
+        let err = hosts.check("admin.example.com").unwrap_err();
+        assert!(err.denied);
+        assert!(err.to_string().contains("--deny-host"), "{err}");
+
+        assert!(HostFilter::default().check("anything.test").is_ok());
+        assert!(filter(&[], &["*.test"]).check("anything.test").is_err());
+    }
+
+    #[test]
+    fn host_lists_block_local_files_unless_allowed() {
+        assert!(HostFilter::default().check_file("notes.html").is_ok());
+        for hosts in [filter(&["example.com"], &[]), filter(&[], &["*.test"])] {
+            let err = hosts.check_file("/etc/passwd").unwrap_err();
+            assert!(err.to_string().contains("--allow-files"), "{err}");
+            let hosts = HostFilter {
+                allow_files: true,
+                ..hosts
+            };
+            assert!(hosts.check_file("/etc/passwd").is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn private_addresses_are_refused() {
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://localhost/",
+            "http://10.1.2.3/",
+            "http://192.168.0.1/",
+            "http://172.16.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+        ] {
+            let url = url::Url::parse(url).unwrap();
+            assert!(check_public(&url, true).await.is_err(), "{url}");
+        }
+        for url in ["http://93.184.216.34/", "http://[2606:2800:220:1::]/"] {
+            let url = url::Url::parse(url).unwrap();
+            assert!(check_public(&url, true).await.is_ok(), "{url}");
+        }
+
+        let url = url::Url::parse("http://localhost:9/").unwrap();
+        let err = check_public(&url, true).await.unwrap_err();
+        assert!(err.to_string().contains("localhost"), "{err}");
+        // Unproxied, names are left to the resolver the connection uses
+        assert!(check_public(&url, false).await.is_ok());
+        let url = url::Url::parse("http://127.0.0.1:9/").unwrap();
+        assert!(check_public(&url, false).await.is_err());
+    }
+
+    #[test]
+    fn cgnat_range_is_private() {
+        for ip in ["100.64.0.1", "100.100.100.200", "100.127.255.255"] {
+            assert!(is_private(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["100.63.255.255", "100.128.0.0"] {
+            assert!(!is_private(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn nat64_addresses_are_judged_by_their_ipv4_address() {
+        assert!(is_private("64:ff9b::a9fe:a9fe".parse().unwrap()));
+        assert!(is_private("64:ff9b::7f00:1".parse().unwrap()));
+        assert!(!is_private("64:ff9b::5db8:d822".parse().unwrap()));
+    }
+
+    #[test]
+    fn ipv4_compatible_addresses_are_judged_by_their_ipv4_address() {
+        assert!(is_private("::169.254.169.254".parse().unwrap()));
+        assert!(is_private("::10.0.0.1".parse().unwrap()));
+        assert!(!is_private("::93.184.216.34".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn resolver_refuses_names_with_only_private_addresses() {
+        let resolve =
+            |resolver: &PublicResolver| resolver.resolve(Name::from_str("localhost").unwrap());
+        let err = match resolve(&PublicResolver::default()).await {
+            Ok(addrs) => panic!("resolved to {:?}", addrs.collect::<Vec<_>>()),
+            Err(err) => err,
+        };
+        assert!(err.is::<SsrfBlocked>(), "{err}");
+
+        let exempt = PublicResolver {
+            exempt: vec!["LOCALHOST".to_string()],
+        };
+        let addrs = resolve(&exempt).await.expect("exempt name resolves");
+        assert!(addrs.into_iter().all(|addr| addr.ip().is_loopback()));
+    }
+
+    #[test]
+    fn proxies_prefer_the_explicit_one() {
+        let explicit = proxies(Some("http://proxy.internal:3128"));
+        assert_eq!(explicit.len(), 1);
+        assert_eq!(explicit[0].host_str(), Some("proxy.internal"));
+        assert_eq!(proxies(Some("proxy:3128"))[0].host_str(), Some("proxy"));
+    }
+
+    #[test]
+    fn malformed_patterns_are_rejected() {
+        for bad in [
+            "",
+            "*",
+            "*.",
+            "**.example.com",
+            "example.com/path",
+            "host:80",
+        ] {
+            assert!(parse_host_pattern(bad).is_err(), "{bad}");
+        }
+    }
+}
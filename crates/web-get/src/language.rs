@@ -0,0 +1,116 @@
+/// Lightweight natural-language detection for extracted page text: the most
+/// common function words of each candidate language, keyed by ISO 639-1 code.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "with", "for", "was", "are",
+            "this", "on", "be", "have", "not", "you", "which", "from", "by", "they", "we", "but",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "des", "et", "est", "une", "dans", "que", "qui", "pour", "pas",
+            "sur", "avec", "sont", "du", "au", "ce", "il", "nous", "vous", "mais", "ou", "été",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "mit", "den", "zu", "von",
+            "sich", "auf", "für", "dem", "es", "auch", "wir", "ich", "wird", "sind", "oder",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "una", "que", "por", "con", "para", "del", "se", "lo",
+            "como", "pero", "más", "su", "sus", "está", "son", "fue", "muy", "este",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "e", "di", "che", "è", "una", "per", "con", "non", "sono", "della",
+            "del", "nel", "anche", "come", "questo", "ma", "più", "alla", "degli",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "não", "uma", "que", "com", "para", "do", "da", "dos", "das",
+            "em", "mais", "por", "é", "são", "mas", "como", "foi", "ao", "também",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "met", "voor", "op", "zijn",
+            "ook", "maar", "aan", "er", "wordt", "naar", "bij", "dit", "worden",
+        ],
+    ),
+];
+
+/// Fewest stopword hits for the leading language before trusting the result.
+const MIN_HITS: usize = 8;
+
+/// How far the leading language must outscore the runner-up.
+const MIN_MARGIN: f64 = 1.5;
+
+/// Detect the language of `text` as an ISO 639-1 code, or `None` when the
+/// text is too short or too ambiguous to call.
+///
+/// Each language scores one point per word of `text` found in its
+/// `STOPWORDS`; a paragraph of prose is dominated by these, so the winner is
+/// usually clear.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|w| stopwords.contains(&w.as_str()))
+                .count();
+            (*lang, hits)
+        })
+        .collect();
+    scores.sort_by_key(|&(_, hits)| std::cmp::Reverse(hits));
+
+    let (lang, best) = scores[0];
+    let runner_up = scores[1].1;
+    if best < MIN_HITS || (best as f64) < runner_up as f64 * MIN_MARGIN {
+        return None;
+    }
+    Some(lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_french_and_english_prose() {
+        let french = "Le chat est sur la table et il dort dans le soleil. Nous pensons que \
+                      les enfants sont partis pour la journée avec leurs amis, mais ce n'est \
+                      pas sûr.";
+        assert_eq!(detect_language(french), Some("fr"));
+
+        let english = "The cat is on the table and it is asleep in the sun. We think that the \
+                       children have gone for the day with their friends, but this is not certain.";
+        assert_eq!(detect_language(english), Some("en"));
+    }
+
+    #[test]
+    fn short_or_unclear_text_is_unknown() {
+        assert_eq!(detect_language("Hello world"), None);
+        assert_eq!(detect_language("fn main() { println!(\"x\"); }"), None);
+        assert_eq!(detect_language(""), None);
+    }
+}
@@ -0,0 +1,458 @@
+//! Fetch → extract → convert as a library. The `web-get` binary is a CLI
+//! over `process_url`; embedders call `fetch_and_extract` for one page.
+pub mod convert;
+pub mod extract;
+pub mod fetch;
+pub mod hashes;
+pub mod hosts;
+pub mod language;
+pub mod sitemap;
+pub mod stats;
+#[cfg(test)]
+pub mod test_server;
+
+pub use agent_tools_common::Event;
+pub use convert::{ConvertOptions, LinkStyle};
+pub use extract::ExtractOptions;
+pub use fetch::{FetchOptions, UserAgents};
+
+use agent_tools_common::ErrorCode;
+use anyhow::{Context, Result};
+use std::sync::Arc;
+
+/// Per-URL settings shared by every task; each field mirrors the CLI flag of
+/// the same name.
+#[derive(Debug, Clone)]
+pub struct ProcessOptions {
+    pub selector: Option<String>,
+    pub selector_all: bool,
+    pub selector_exclude: Vec<String>,
+    pub keep_images: bool,
+    pub keep_tags: Vec<String>,
+    pub link_style: LinkStyle,
+    pub include_raw_html: bool,
+    pub frontmatter: bool,
+    pub normalize_urls: bool,
+    pub follow_canonical: bool,
+    pub follow_meta_refresh: bool,
+    pub timeout: std::time::Duration,
+    pub max_bytes: usize,
+    pub max_redirects: usize,
+    pub min_words: u32,
+    pub no_soft_404: bool,
+    pub accept_language: Option<String>,
+    pub auth: Option<fetch::Auth>,
+    pub hosts: hosts::HostFilter,
+    pub allow_private: bool,
+    pub proxy: Option<String>,
+    pub user_agents: fetch::UserAgents,
+}
+
+impl Default for ProcessOptions {
+    /// The CLI defaults.
+    fn default() -> Self {
+        Self {
+            selector: None,
+            selector_all: false,
+            selector_exclude: Vec::new(),
+            keep_images: false,
+            keep_tags: Vec::new(),
+            link_style: LinkStyle::Inline,
+            include_raw_html: false,
+            frontmatter: false,
+            normalize_urls: false,
+            follow_canonical: false,
+            follow_meta_refresh: false,
+            timeout: std::time::Duration::from_secs(15),
+            max_bytes: 10 * 1024 * 1024,
+            max_redirects: 10,
+            min_words: 0,
+            no_soft_404: false,
+            accept_language: None,
+            auth: None,
+            hosts: hosts::HostFilter::default(),
+            allow_private: false,
+            proxy: None,
+            user_agents: fetch::UserAgents::default(),
+        }
+    }
+}
+
+/// One processed URL: the event to emit plus fetch metadata for `--stats`.
+pub struct Outcome {
+    pub event: Event,
+    pub bytes: usize,
+    pub truncated: bool,
+}
+
+/// A client builder set up the way `fetch` expects: redirects are followed
+/// (and recorded) by `fetch` itself, so reqwest must not follow them. Unless
+/// `opts.allow_private`, host names resolve only to public addresses; the
+/// proxy hosts are exempt, as requests through them are checked up front.
+pub fn client_builder(opts: &ProcessOptions) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if opts.allow_private {
+        return builder;
+    }
+    let exempt = hosts::proxies(opts.proxy.as_deref())
+        .iter()
+        .filter_map(|proxy| proxy.host_str().map(str::to_string))
+        .collect();
+    builder.dns_resolver(Arc::new(hosts::PublicResolver { exempt }))
+}
+
+/// Fetch `url` and convert it the way the CLI does, returning its
+/// `Event::Document`. `client` should come from `client_builder`. A page
+/// judged a soft 404 comes back as `Ok(Event::Error)`; fetch and conversion
+/// failures are `Err`.
+pub async fn fetch_and_extract(
+    client: &reqwest::Client,
+    url: &str,
+    opts: &ProcessOptions,
+) -> Result<Event> {
+    let outcome = process_url_inner(client, url, &Arc::new(opts.clone())).await?;
+    Ok(outcome.event)
+}
+
+/// Fetch and convert `url`, turning any failure into an error event.
+pub async fn process_url(
+    client: &reqwest::Client,
+    url: &str,
+    opts: &Arc<ProcessOptions>,
+) -> Outcome {
+    match process_url_inner(client, url, opts).await {
+        Ok(outcome) => outcome,
+        Err(e) => process_error(url, e),
+    }
+}
+
+/// The error event for a URL that could not be fetched or processed.
+pub fn process_error(url: &str, e: anyhow::Error) -> Outcome {
+    Outcome {
+        event: Event::Error {
+            code: error_code(&e),
+            message: format!("{:#}", e),
+            path_or_url: Some(url.to_string()),
+        },
+        bytes: 0,
+        truncated: false,
+    }
+}
+
+/// The fetch settings within `opts`.
+pub fn fetch_options(opts: &ProcessOptions) -> fetch::FetchOptions {
+    fetch::FetchOptions {
+        timeout: opts.timeout,
+        max_bytes: opts.max_bytes,
+        user_agents: opts.user_agents.clone(),
+        max_redirects: opts.max_redirects,
+        accept_language: opts.accept_language.clone(),
+        auth: opts.auth.clone(),
+        hosts: opts.hosts.clone(),
+        allow_private: opts.allow_private,
+        proxied: !hosts::proxies(opts.proxy.as_deref()).is_empty(),
+    }
+}
+
+/// Classify a failed fetch: timeouts from either our deadline or reqwest's
+/// own are reported separately from other network errors, as are hosts the
+/// allow/deny lists or the private-address guard refused.
+fn error_code(e: &anyhow::Error) -> ErrorCode {
+    let blocked = |cause: &(dyn std::error::Error + 'static)| {
+        cause.is::<hosts::HostBlocked>() || cause.is::<hosts::FileBlocked>()
+    };
+    if e.chain().any(blocked) {
+        return ErrorCode::HostBlocked;
+    }
+    if e.chain().any(|cause| cause.is::<hosts::SsrfBlocked>()) {
+        return ErrorCode::SsrfBlocked;
+    }
+    let timed_out = e.chain().any(|cause| {
+        cause.is::<fetch::Timeout>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout)
+    });
+    if timed_out {
+        ErrorCode::Timeout
+    } else {
+        ErrorCode::Fetch
+    }
+}
+
+/// Canonical links and meta refreshes followed per input URL before settling
+/// on the last page.
+const MAX_FOLLOW_HOPS: usize = 5;
+
+/// Fetch and convert `url`. Under `--follow-meta-refresh`, a page that
+/// redirects client-side is replaced by its target; under
+/// `--follow-canonical`, a document whose canonical URL points elsewhere is
+/// replaced by that page. Pages already visited are never fetched again, and
+/// the pages left behind are prepended to the final document's `redirects`.
+async fn process_url_inner(
+    client: &reqwest::Client,
+    url: &str,
+    opts: &Arc<ProcessOptions>,
+) -> Result<Outcome> {
+    let (mut outcome, mut refresh) = fetch_and_convert(client, url, opts).await?;
+    if !opts.follow_canonical && refresh.is_none() {
+        return Ok(outcome);
+    }
+
+    for _ in 0..MAX_FOLLOW_HOPS {
+        let Event::Document {
+            url: page_url,
+            canonical_url,
+            redirects,
+            ..
+        } = &outcome.event
+        else {
+            break;
+        };
+        let (target, reason) = match (refresh.take(), canonical_url) {
+            (Some(target), _) => (target, "meta refresh"),
+            (None, Some(canonical)) if opts.follow_canonical => {
+                (canonical.clone(), "canonical URL")
+            }
+            _ => break,
+        };
+        // A followed page may only lead to another web page, never to a
+        // local file
+        if !url::Url::parse(&target).is_ok_and(|t| matches!(t.scheme(), "http" | "https")) {
+            break;
+        }
+        // Every page fetched so far, oldest first; also the loop guard
+        let mut chain = redirects.clone();
+        chain.push(page_url.clone());
+        if std::iter::once(url)
+            .chain(chain.iter().map(String::as_str))
+            .any(|seen| same_page(seen, &target))
+        {
+            break;
+        }
+
+        let (mut next, next_refresh) = fetch_and_convert(client, &target, opts)
+            .await
+            .with_context(|| format!("following {} {}", reason, target))?;
+        refresh = next_refresh;
+        if let Event::Document { redirects, .. } = &mut next.event {
+            redirects.splice(0..0, chain);
+        }
+        next.bytes += outcome.bytes;
+        outcome = next;
+    }
+    Ok(outcome)
+}
+
+/// Whether two URLs name the same page, ignoring fragments and trivial
+/// spelling differences such as a missing root slash.
+fn same_page(a: &str, b: &str) -> bool {
+    let normalize = |u: &str| {
+        url::Url::parse(u).map(|mut parsed| {
+            parsed.set_fragment(None);
+            parsed.to_string()
+        })
+    };
+    match (normalize(a), normalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Fetch and convert `url`, along with its client-side redirect target when
+/// `--follow-meta-refresh` is on and the page has one.
+async fn fetch_and_convert(
+    client: &reqwest::Client,
+    url: &str,
+    opts: &Arc<ProcessOptions>,
+) -> Result<(Outcome, Option<String>)> {
+    let fetch_result = fetch::fetch(client, url, &fetch_options(opts)).await?;
+    let bytes = fetch_result.body.len();
+    let truncated = fetch_result.truncated;
+
+    // Parse media type
+    let media_type = if let Ok(mime) = fetch_result.content_type.parse::<mime::Mime>() {
+        mime.essence_str().to_string()
+    } else {
+        "application/octet-stream".to_string()
+    };
+
+    // Handle PDFs as stubs
+    if media_type.starts_with("application/pdf") {
+        let hash = blake3::hash(&fetch_result.body).to_hex().to_string();
+        let event = Event::Document {
+            url: fetch_result.final_url,
+            title: String::new(),
+            byline: None,
+            description: None,
+            text_md: String::new(),
+            word_count: 0,
+            links: vec![],
+            canonical_url: None,
+            media_type,
+            hash,
+            redirects: fetch_result.redirects,
+            content_language: fetch_result.content_language,
+            language: None,
+            raw_html: None,
+        };
+        let outcome = Outcome {
+            event,
+            bytes,
+            truncated,
+        };
+        return Ok((outcome, None));
+    }
+
+    // Decoding, parsing, and conversion are CPU-bound; keep them off the
+    // async workers so other fetches keep making progress
+    let opts = Arc::clone(opts);
+    match tokio::task::spawn_blocking(move || convert_html(fetch_result, &opts)).await {
+        Ok(result) => result,
+        // Surface as a panic of this URL's task, as if it had run inline
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Turn a fetched HTML body into a `Document` (or soft-404 error) event, plus
+/// the page's refresh target under `--follow-meta-refresh`.
+fn convert_html(
+    fetch_result: fetch::FetchResult,
+    opts: &ProcessOptions,
+) -> Result<(Outcome, Option<String>)> {
+    let bytes = fetch_result.body.len();
+    let truncated = fetch_result.truncated;
+
+    // Decode HTML
+    let charset = convert::parse_charset(&fetch_result.content_type);
+    let html = convert::decode_to_utf8(&fetch_result.body, charset.as_deref())?;
+
+    // Extract content
+    let extract_opts = extract::ExtractOptions {
+        selector: opts.selector.clone(),
+        selector_all: opts.selector_all,
+        exclude: opts.selector_exclude.clone(),
+    };
+    let extracted = extract::extract_content(&html, &fetch_result.final_url, &extract_opts)?;
+    let refresh = extracted.refresh_url.filter(|_| opts.follow_meta_refresh);
+
+    // Convert to Markdown
+    let convert_opts = convert::ConvertOptions {
+        keep_images: opts.keep_images,
+        keep_tags: opts.keep_tags.clone(),
+        base_url: fetch_result.final_url.clone(),
+        link_style: opts.link_style,
+        normalize_urls: opts.normalize_urls,
+    };
+    let converted = convert::convert_to_markdown(&extracted.main_html, &convert_opts)?;
+
+    let word_count = convert::count_prose_words(&converted.markdown);
+    let language = language::detect_language(&converted.markdown).map(str::to_string);
+
+    // Thin "200 OK" pages are usually not-found or login walls, not content.
+    // A redirect shell is thin too, but it is about to be replaced.
+    let soft_404 = (refresh.is_none() && !opts.no_soft_404).then(|| {
+        extract::soft_404_reason(
+            &extracted.title,
+            &converted.markdown,
+            word_count,
+            opts.min_words,
+        )
+    });
+    if let Some(reason) = soft_404.flatten() {
+        let outcome = Outcome {
+            event: Event::Error {
+                code: ErrorCode::Soft404,
+                message: format!("soft 404: {}", reason),
+                path_or_url: Some(fetch_result.final_url),
+            },
+            bytes,
+            truncated,
+        };
+        return Ok((outcome, None));
+    }
+
+    // `hash` and `word_count` already describe the body alone
+    let text_md = if opts.frontmatter {
+        let front = convert::frontmatter(&[
+            ("title", extracted.title.as_str().into()),
+            ("url", fetch_result.final_url.as_str().into()),
+            ("byline", extracted.byline.as_deref().into()),
+            ("canonical_url", extracted.canonical_url.as_deref().into()),
+            ("word_count", word_count.into()),
+        ]);
+        front + &converted.markdown
+    } else {
+        converted.markdown
+    };
+
+    let event = Event::Document {
+        url: fetch_result.final_url,
+        title: extracted.title,
+        byline: extracted.byline,
+        description: extracted.description,
+        text_md,
+        word_count,
+        links: converted.links,
+        canonical_url: extracted.canonical_url,
+        media_type: "text/html".to_string(),
+        hash: converted.hash,
+        redirects: fetch_result.redirects,
+        content_language: fetch_result.content_language,
+        language,
+        raw_html: opts.include_raw_html.then_some(converted.html),
+    };
+    let outcome = Outcome {
+        event,
+        bytes,
+        truncated,
+    };
+    Ok((outcome, refresh))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Response, TestServer};
+
+    #[tokio::test]
+    async fn fetch_and_extract_returns_a_document() {
+        let server = TestServer::start(|req| match req.path.as_str() {
+            "/post" => Response::html(
+                "<html><head><title>Post</title></head><body>\
+                 <article><h1>Post</h1><p>Body text with a <a href=\"/next\">link</a>.</p></article>\
+                 </body></html>",
+            ),
+            _ => Response::status(404),
+        })
+        .await;
+        let opts = ProcessOptions {
+            allow_private: true,
+            ..ProcessOptions::default()
+        };
+        let client = client_builder(&opts).build().unwrap();
+
+        let event = fetch_and_extract(&client, &server.url("/post"), &opts)
+            .await
+            .expect("document");
+        let Event::Document {
+            url,
+            title,
+            text_md,
+            links,
+            ..
+        } = event
+        else {
+            panic!("expected a document, got {event:?}");
+        };
+        assert_eq!(url, server.url("/post"));
+        assert_eq!(title, "Post");
+        assert!(text_md.contains("Body text"), "{text_md}");
+        assert_eq!(links, [server.url("/next")]);
+
+        assert!(fetch_and_extract(&client, &server.url("/gone"), &opts)
+            .await
+            .is_err());
+    }
+}
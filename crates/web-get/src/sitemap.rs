@@ -0,0 +1,233 @@
+/// Expansion of sitemap.xml inputs (sitemaps.org protocol) into page URLs.
+use crate::fetch::{self, FetchOptions};
+use regex::Regex;
+use reqwest::Client;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Sitemap indexes nested deeper than this are not followed.
+const MAX_NESTING: usize = 5;
+
+/// A parsed sitemap document.
+#[derive(Debug, PartialEq)]
+pub enum Sitemap {
+    /// `<urlset>`: page URLs.
+    UrlSet(Vec<String>),
+    /// `<sitemapindex>`: URLs of further sitemaps.
+    Index(Vec<String>),
+}
+
+/// The input list with sitemaps replaced by the pages they list.
+#[derive(Default)]
+pub struct Expansion {
+    pub urls: Vec<String>,
+    /// Sitemaps that could not be fetched, with the reason.
+    pub errors: Vec<(String, anyhow::Error)>,
+    /// `dedup_key`s of `urls`.
+    keys: HashSet<String>,
+}
+
+impl Expansion {
+    /// Add a page URL unless another spelling of it is already listed.
+    fn push(&mut self, url: String) {
+        if self.keys.insert(dedup_key(&url)) {
+            self.urls.push(url);
+        } else {
+            tracing::debug!(url = %url, "skipping repeated URL");
+        }
+    }
+}
+
+/// The form two URLs share when they name the same page: scheme and host
+/// lowercased, default port, fragment and trailing slash dropped, so
+/// `HTTP://X.com:80/` and `http://x.com` agree. Unparseable input is only
+/// trimmed.
+pub fn dedup_key(url: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+    parsed.set_fragment(None);
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+    let mut key = parsed.to_string();
+    // A root path always serializes as "/"; drop it when nothing follows
+    if parsed.path() == "/" && parsed.query().is_none() {
+        key.pop();
+    }
+    key
+}
+
+/// Whether `url` names a sitemap by convention (`…sitemap.xml`,
+/// `…sitemap_index.xml`).
+pub fn is_sitemap_url(url: &str) -> bool {
+    let path = url::Url::parse(url)
+        .map(|u| u.path().to_ascii_lowercase())
+        .unwrap_or_else(|_| url.to_ascii_lowercase());
+    path.ends_with("sitemap.xml") || path.ends_with("sitemap_index.xml")
+}
+
+/// Pull the `<loc>` entries out of a sitemap or sitemap index.
+pub fn parse_sitemap(xml: &str) -> Sitemap {
+    static LOC: OnceLock<Regex> = OnceLock::new();
+    static INDEX: OnceLock<Regex> = OnceLock::new();
+    let loc = LOC.get_or_init(|| {
+        Regex::new(r"(?s)<(?:[\w-]+:)?loc\b[^>]*>(.*?)</(?:[\w-]+:)?loc\s*>").expect("valid regex")
+    });
+    let index =
+        INDEX.get_or_init(|| Regex::new(r"<(?:[\w-]+:)?sitemapindex\b").expect("valid regex"));
+
+    let urls = loc
+        .captures_iter(xml)
+        .map(|c| unescape_xml(strip_cdata(c[1].trim())))
+        .filter(|u| !u.is_empty())
+        .collect();
+    if index.is_match(xml) {
+        Sitemap::Index(urls)
+    } else {
+        Sitemap::UrlSet(urls)
+    }
+}
+
+fn strip_cdata(text: &str) -> &str {
+    text.strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .map_or(text, str::trim)
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Replace every sitemap in `inputs` (all of them when `force`, otherwise
+/// those named like one) with the page URLs it lists, following nested
+/// indexes depth-first so the output keeps document order. A page listed
+/// twice, under any spelling `dedup_key` equates, is kept where it first
+/// appears. At most `max_urls` distinct URLs are returned in total; expansion
+/// stops once it is reached.
+pub async fn expand(
+    client: &Client,
+    inputs: Vec<String>,
+    force: bool,
+    opts: &FetchOptions,
+    max_urls: Option<usize>,
+) -> Expansion {
+    let max_urls = max_urls.unwrap_or(usize::MAX);
+    let mut expansion = Expansion::default();
+    let mut seen = HashSet::new();
+
+    for input in inputs {
+        if expansion.urls.len() >= max_urls {
+            break;
+        }
+        if !(force || is_sitemap_url(&input)) {
+            expansion.push(input);
+            continue;
+        }
+
+        // Depth-first over nested indexes; children are pushed in reverse so
+        // they pop in document order
+        let mut stack = vec![(input, 0)];
+        while let Some((sitemap_url, depth)) = stack.pop() {
+            if expansion.urls.len() >= max_urls {
+                break;
+            }
+            if depth > MAX_NESTING || !seen.insert(sitemap_url.clone()) {
+                continue;
+            }
+            let body = match fetch::fetch(client, &sitemap_url, opts).await {
+                Ok(result) => String::from_utf8_lossy(&result.body).into_owned(),
+                Err(e) => {
+                    expansion.errors.push((sitemap_url, e));
+                    continue;
+                }
+            };
+            match parse_sitemap(&body) {
+                Sitemap::UrlSet(urls) => {
+                    for url in urls {
+                        if expansion.urls.len() >= max_urls {
+                            break;
+                        }
+                        expansion.push(url);
+                    }
+                }
+                Sitemap::Index(children) => {
+                    stack.extend(children.into_iter().rev().map(|c| (c, depth + 1)));
+                }
+            }
+        }
+    }
+
+    expansion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_key_ignores_case_default_ports_and_trailing_slashes() {
+        let key = dedup_key("http://x.com");
+        for same in [
+            "http://x.com:80/",
+            "HTTP://X.COM/",
+            "http://x.com/#top",
+            " http://x.com ",
+        ] {
+            assert_eq!(dedup_key(same), key, "{same}");
+        }
+        assert_eq!(dedup_key("https://x.com/a/"), dedup_key("https://x.com/a"));
+        assert_ne!(
+            dedup_key("https://x.com:8443/"),
+            dedup_key("https://x.com/")
+        );
+        assert_ne!(dedup_key("https://x.com/A"), dedup_key("https://x.com/a"));
+        assert_ne!(dedup_key("https://x.com/?q=1"), dedup_key("https://x.com/"));
+        assert_eq!(dedup_key("not a url"), "not a url");
+    }
+
+    #[test]
+    fn urlset_entries_are_listed_in_order() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/a</loc><lastmod>2024-01-01</lastmod></url>
+  <url><loc>
+    https://example.com/b?x=1&amp;y=2
+  </loc></url>
+  <url><loc><![CDATA[https://example.com/c]]></loc></url>
+</urlset>"#;
+        assert_eq!(
+            parse_sitemap(xml),
+            Sitemap::UrlSet(vec![
+                "https://example.com/a".to_string(),
+                "https://example.com/b?x=1&y=2".to_string(),
+                "https://example.com/c".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn sitemap_index_lists_child_sitemaps() {
+        let xml = r#"<sm:sitemapindex xmlns:sm="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sm:sitemap><sm:loc>https://example.com/posts-sitemap.xml</sm:loc></sm:sitemap>
+</sm:sitemapindex>"#;
+        assert_eq!(
+            parse_sitemap(xml),
+            Sitemap::Index(vec!["https://example.com/posts-sitemap.xml".to_string()])
+        );
+    }
+
+    #[test]
+    fn sitemap_urls_are_recognized_by_name() {
+        assert!(is_sitemap_url("https://example.com/sitemap.xml"));
+        assert!(is_sitemap_url(
+            "https://example.com/blog/post-sitemap.xml?v=2"
+        ));
+        assert!(is_sitemap_url("https://example.com/SITEMAP_INDEX.XML"));
+        assert!(!is_sitemap_url("https://example.com/feed.xml"));
+        assert!(!is_sitemap_url("https://example.com/sitemap.xml/page"));
+    }
+}
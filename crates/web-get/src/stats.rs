@@ -0,0 +1,47 @@
+/// Running totals for the `--stats` summary event.
+use agent_tools_common::Event;
+use serde::Serialize;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Stats {
+    pub documents: u64,
+    pub errors: u64,
+    pub truncated: u64,
+    pub bytes: u64,
+    pub words: u64,
+    /// Documents dropped by `--dedupe`.
+    pub duplicates: u64,
+    /// Documents replaced by an `unchanged` event under `--skip-unchanged`.
+    pub unchanged: u64,
+}
+
+impl Stats {
+    /// Fold one emitted event and its fetch metadata into the totals.
+    pub fn record(&mut self, event: &Event, bytes: usize, truncated: bool) {
+        match event {
+            Event::Document { word_count, .. } => {
+                self.documents += 1;
+                self.words += u64::from(*word_count);
+            }
+            Event::Unchanged { .. } => self.unchanged += 1,
+            Event::Error { .. } => self.errors += 1,
+            _ => {}
+        }
+        self.bytes += bytes as u64;
+        if truncated {
+            self.truncated += 1;
+        }
+    }
+
+    pub fn into_event(self) -> Event {
+        let message = format!(
+            "{} documents, {} errors, {} truncated, {} bytes, {} words",
+            self.documents, self.errors, self.truncated, self.bytes, self.words
+        );
+        Event::Summary {
+            tool: "web-get".to_string(),
+            message,
+            stats: serde_json::to_value(&self).unwrap_or_default(),
+        }
+    }
+}
@@ -6,6 +6,7 @@ import { spawn } from "child_process";
  * NDJSON event types from agent-tools
  */
 interface MatchEvent {
+  v: number;
   type: "match";
   lang: string;
   path: string;
@@ -14,36 +15,64 @@ interface MatchEvent {
   chunk_id: string;
   score: number;
   excerpt: string;
+  enclosing?: string | null;
   capture: {
     callee?: string | null;
+    callee_path?: string | null;
     object?: string | null;
+    member?: string | null;
     module?: string | null;
     name?: string | null;
-    attr?: string | null;
+    type?: string | null;
+    trait?: string | null;
+    methods?: string[] | null;
+    embeds?: string[] | null;
+    generics?: string | null;
+    aliases?: string[] | null;
+    members?: string[] | null;
+    args?: string | null;
+    target?: string | null;
+    value?: string | null;
+    groups?: Record<string, string> | null;
+    raw?: Record<string, string | string[]>;
   };
 }
 
 interface DocumentEvent {
+  v: number;
   type: "document";
   url: string;
   title: string;
   byline?: string | null;
+  description?: string | null;
   text_md: string;
   word_count: number;
   links: string[];
   canonical_url?: string | null;
   media_type: string;
   hash: string;
+  redirects: string[];
+  content_language?: string | null;
+  language?: string | null;
+  raw_html?: string;
+}
+
+interface UnchangedEvent {
+  v: number;
+  type: "unchanged";
+  url: string;
+  hash: string;
 }
 
 interface ErrorEvent {
+  v: number;
   type: "error";
   code: string;
   message: string;
   path_or_url: string;
 }
 
-type AgentToolEvent = MatchEvent | DocumentEvent | ErrorEvent;
+type AgentToolEvent = MatchEvent | DocumentEvent | UnchangedEvent | ErrorEvent;
 
 /**
  * Execute a command and parse NDJSON output